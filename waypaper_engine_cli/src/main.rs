@@ -1,9 +1,21 @@
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Local};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use linux_ipc::IpcChannel;
 use std::io;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
 use tracing::{debug, error, info};
-use waypaper_engine_shared::ipc::{IPCError, IPCRequest, IPCResponse};
+use waypaper_engine_formats::scene_package::ScenePackage;
+use waypaper_engine_formats::tex_file::TexFile;
+use waypaper_engine_shared::config::{Config, ConfigError, config_path};
+use waypaper_engine_shared::ipc::{
+    IPCError, IPCEvent, IPCRequest, IPCResponse, OutputStatus, Playlist, PlaylistOrder, Profile,
+    ScalingMode, ScheduledSwitch,
+};
+use waypaper_engine_shared::library::{Library, LibraryEntry};
+use waypaper_engine_shared::project::{PropertyValue, WEProject, WallpaperType};
 
 #[derive(Parser)]
 struct Args {
@@ -17,6 +29,12 @@ struct Args {
         global = true
     )]
     json_output: bool,
+    #[arg(
+        long,
+        help = "Path of the daemon IPC socket (defaults to $XDG_RUNTIME_DIR/waypaper-engine-$WAYLAND_DISPLAY.sock)",
+        global = true
+    )]
+    socket: Option<PathBuf>,
     #[command(flatten)]
     verbosity: Verbosity<InfoLevel>,
 }
@@ -25,18 +43,242 @@ struct Args {
 enum Commands {
     /// Change the wallpaper on the given screen
     Set {
-        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        /// The screen identifier (e.g. "DP-1", "HDMI-0"), "*" for every screen or a glob pattern
+        /// such as "DP-*"
         screen: String,
         /// The wallpaper ID to set
+        #[arg(required_unless_present = "path")]
+        id: Option<u64>,
+        /// Directory of a wallpaper outside of the workshop (must contain a project.json)
+        #[arg(long, conflicts_with = "id")]
+        path: Option<PathBuf>,
+    },
+    /// List the installed wallpapers, without involving the daemon
+    List(ListArgs),
+    /// Show the details of an installed wallpaper, including its properties
+    Info {
+        /// The wallpaper ID
         id: u64,
     },
     /// List all available outputs
     Outputs,
+    /// Show what is currently displayed on each output
+    Status,
+    /// Pause the wallpaper playback on the given screen, keeping the current frame displayed
+    Pause {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+    /// Resume the wallpaper playback on the given screen
+    Resume {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+    /// Pause the wallpaper playback on the given screen if it is playing, resume it otherwise
+    #[clap(name = "toggle-pause", aliases = &["toggle"])]
+    TogglePause {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+    /// Change how the wallpaper is scaled when its aspect ratio doesn't match the screen one
+    Scaling {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+        /// One of fill, fit, stretch, center or tile
+        mode: ScalingMode,
+    },
+    /// Limit the framerate of the given screen, or of every screen
+    #[clap(name = "fps-cap")]
+    FpsCap {
+        /// Maximum frames per second, 0 removes the limit
+        fps: u32,
+        /// The screen identifier (e.g. "DP-1", "HDMI-0"), applies to every screen if omitted
+        #[arg(long)]
+        screen: Option<String>,
+    },
+    /// Change a property of the wallpaper displayed on the given screen
+    #[clap(name = "set-property")]
+    SetProperty {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+        /// The property name, as found in the wallpaper project.json
+        key: String,
+        /// The new value: a number for sliders, true/false, "r g b" or #rrggbb for colors,
+        /// the option value or label for combos
+        value: String,
+    },
+    /// Save the frame currently displayed on the given screen as a PNG image
+    Screenshot {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+        /// Where to write the PNG image
+        path: PathBuf,
+    },
+    /// Skip to the next wallpaper of the playlist running on the given screen
+    Next {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+    /// Go back to the previous wallpaper of the playlist running on the given screen
+    #[clap(aliases = &["prev"])]
+    Previous {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+    /// Manage the playlists rotating wallpapers
+    #[command(subcommand)]
+    Playlist(PlaylistCommands),
+    /// Manage the profiles, named sets of wallpapers and settings for every output
+    #[command(subcommand)]
+    Profile(ProfileCommands),
+    /// Inspect the wallpaper schedule ($XDG_CONFIG_HOME/waypaper_engine/schedule.conf)
+    #[command(subcommand)]
+    Schedule(ScheduleCommands),
+    /// Inspect the daemon configuration ($XDG_CONFIG_HOME/waypaper_engine/config.toml)
+    #[command(subcommand)]
+    Config(ConfigCommands),
+    /// Inspect and unpack the scene packages (scene.pkg) of scene wallpapers
+    #[command(subcommand)]
+    Pkg(PkgCommands),
+    /// Inspect and convert the textures (.tex) of scene wallpapers
+    #[command(subcommand)]
+    Tex(TexCommands),
+    /// Make the daemon read its configuration and saved wallpapers again
+    Reload,
+    /// Print daemon events (outputs and wallpaper changes) as they happen
+    Watch,
     /// Kill the daemon
     #[clap(name = "kill-daemon", aliases = &["killdaemon", "kill"])]
     KillDaemon,
 }
 
+#[derive(clap::Args)]
+struct ListArgs {
+    /// Only list the wallpapers of this type (video, scene, web or preset)
+    #[arg(long = "type")]
+    wallpaper_type: Option<WallpaperType>,
+    /// Only list the wallpapers with this tag, can be repeated
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Only list the wallpapers with this content rating (e.g. "Everyone", "Questionable" or
+    /// "Mature")
+    #[arg(long)]
+    rating: Option<String>,
+    /// Only list the wallpapers whose title contains this text
+    #[arg(long)]
+    title: Option<String>,
+    /// Sort the wallpapers by this field
+    #[arg(long, value_enum, default_value_t = SortKey::Id)]
+    sort: SortKey,
+    /// Reverse the sorting order
+    #[arg(long, default_value_t = false)]
+    reverse: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortKey {
+    Id,
+    Title,
+    Type,
+    Size,
+    Modified,
+}
+
+#[derive(Subcommand)]
+enum PlaylistCommands {
+    /// Create a playlist, or replace the one with the same name
+    Create {
+        name: String,
+        /// The wallpaper IDs, in playing order
+        #[arg(required = true)]
+        ids: Vec<u64>,
+        /// Seconds before switching to the next wallpaper
+        #[arg(long, default_value_t = 600)]
+        interval: u64,
+        /// Play the wallpapers in a random order
+        #[arg(long, default_value_t = false)]
+        shuffle: bool,
+    },
+    /// Delete a playlist, stopping it wherever it is running
+    Delete { name: String },
+    /// List the saved playlists
+    List,
+    /// Start a playlist on the given screen
+    Start {
+        name: String,
+        /// The screen identifier (e.g. "DP-1", "HDMI-0"), "*" for every screen or a glob pattern
+        /// such as "DP-*"
+        screen: String,
+    },
+    /// Stop the playlist running on the given screen, keeping the current wallpaper displayed
+    Stop {
+        /// The screen identifier (e.g. "DP-1", "HDMI-0")
+        screen: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Save the wallpaper and settings of every output, or replace the profile with the same name
+    Save { name: String },
+    /// Restore the wallpaper and settings of the outputs saved in the profile
+    Load { name: String },
+    /// Delete a profile
+    Delete { name: String },
+    /// List the saved profiles
+    List,
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// List the wallpaper switches planned for the next 24 hours
+    List,
+}
+
+#[derive(Subcommand)]
+enum PkgCommands {
+    /// List the files of a scene package
+    List {
+        /// The scene.pkg file
+        path: PathBuf,
+    },
+    /// Extract the files of a scene package into a directory, created if missing
+    Extract {
+        /// The scene.pkg file
+        path: PathBuf,
+        /// Where to write the files
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TexCommands {
+    /// Print the header, images, mipmaps and spritesheet frames of a texture
+    Info {
+        /// The .tex file
+        path: PathBuf,
+    },
+    /// Convert a texture to a standard image, in the format given by the output extension
+    Convert {
+        /// The .tex file
+        path: PathBuf,
+        /// The image to write (e.g. texture.png)
+        output: PathBuf,
+        /// Write each spritesheet frame to its own image, numbered after the output name
+        #[arg(long)]
+        all_frames: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check that a configuration file is valid, without involving the daemon
+    Check {
+        /// The file to check (defaults to $XDG_CONFIG_HOME/waypaper_engine/config.toml)
+        path: Option<PathBuf>,
+    },
+}
+
 fn main() {
     let args = Args::parse();
 
@@ -48,7 +290,45 @@ fn main() {
             .init()
     }
 
-    let mut channel = match IpcChannel::connect("/tmp/waypaper-engine.sock") {
+    // Don't need the daemon
+    match &args.commands {
+        Commands::Config(ConfigCommands::Check { path }) => {
+            check_config(path.as_deref(), args.json_output);
+            return;
+        }
+        Commands::List(list_args) => {
+            list_wallpapers(list_args, args.json_output);
+            return;
+        }
+        Commands::Info { id } => {
+            print_wallpaper_info(*id, args.json_output);
+            return;
+        }
+        Commands::Pkg(command) => {
+            run_pkg_command(command, args.json_output);
+            return;
+        }
+        Commands::Tex(command) => {
+            run_tex_command(command, args.json_output);
+            return;
+        }
+        _ => {}
+    }
+
+    let socket_path = args.socket.clone().unwrap_or_else(|| {
+        Config::load(None)
+            .ok()
+            .map_or_else(waypaper_engine_shared::get_socket_path, |config| {
+                config.socket_path()
+            })
+    });
+
+    let Some(socket_path) = socket_path.to_str() else {
+        print_daemon_connection_error("The socket path is not valid UTF-8", args.json_output);
+        return;
+    };
+
+    let mut channel = match IpcChannel::connect(socket_path) {
         Ok(channel) => channel,
         Err(err) => {
             print_daemon_connection_error(&err.to_string(), args.json_output);
@@ -66,19 +346,251 @@ fn main() {
                 args.json_output,
             );
         }
-        Commands::Set { screen, id } => {
-            info!("Setting wallpaper with ID {} on screen {}", id, screen);
+        Commands::Status => {
+            if !args.json_output {
+                debug!("Sending request to the daemon...");
+            }
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::GetStatus),
+                args.json_output,
+            );
+        }
+        Commands::Watch => {
+            if !args.json_output {
+                debug!("Subscribing to daemon events...");
+            }
+
+            match channel.send::<_, IPCResponse>(IPCRequest::Subscribe) {
+                Ok(Some(IPCResponse::Subscribed { socket })) => {
+                    watch_events(&socket, args.json_output)
+                }
+                response => handle_ipc_response(response, args.json_output),
+            }
+        }
+        Commands::Set { screen, id, path } => {
+            let request = match (id, path) {
+                (Some(id), _) => {
+                    info!("Setting wallpaper with ID {} on screen {}", id, screen);
+                    IPCRequest::SetWallpaper {
+                        screen: screen.clone(),
+                        id: *id,
+                    }
+                }
+                (None, Some(path)) => {
+                    let Some(path) = absolute_path(path, args.json_output) else {
+                        return;
+                    };
+
+                    info!(
+                        "Setting wallpaper from {} on screen {}",
+                        path.to_string_lossy(),
+                        screen
+                    );
+                    IPCRequest::SetWallpaperPath {
+                        screen: screen.clone(),
+                        path,
+                    }
+                }
+                (None, None) => unreachable!("clap requires either an id or a path"),
+            };
+
             if !args.json_output {
                 debug!("Sending request to the daemon...");
             }
+            handle_ipc_response(channel.send::<_, IPCResponse>(request), args.json_output);
+        }
+        Commands::Pause { screen } => {
+            info!("Pausing playback on screen {}", screen);
             handle_ipc_response(
-                channel.send::<_, IPCResponse>(IPCRequest::SetWallpaper {
+                channel.send::<_, IPCResponse>(IPCRequest::Pause {
                     screen: screen.clone(),
-                    id: *id,
                 }),
                 args.json_output,
             );
         }
+        Commands::Resume { screen } => {
+            info!("Resuming playback on screen {}", screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::Resume {
+                    screen: screen.clone(),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::TogglePause { screen } => {
+            info!("Toggling playback on screen {}", screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::TogglePause {
+                    screen: screen.clone(),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::Scaling { screen, mode } => {
+            info!("Setting scaling mode {} on screen {}", mode, screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::SetScaling {
+                    screen: screen.clone(),
+                    mode: *mode,
+                }),
+                args.json_output,
+            );
+        }
+        Commands::FpsCap { fps, screen } => {
+            let target = screen.as_deref().unwrap_or("every screen");
+            if *fps == 0 {
+                info!("Removing FPS cap of {}", target);
+            } else {
+                info!("Capping {} at {} FPS", target, fps);
+            }
+
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::SetFpsCap {
+                    screen: screen.clone(),
+                    fps: (*fps != 0).then_some(*fps),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::SetProperty { screen, key, value } => {
+            info!("Setting property {} to {} on screen {}", key, value, screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::SetProperty {
+                    screen: screen.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::Screenshot { screen, path } => {
+            let Some(path) = absolute_path(path, args.json_output) else {
+                return;
+            };
+
+            info!(
+                "Saving screenshot of screen {} to {}",
+                screen,
+                path.to_string_lossy()
+            );
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::Screenshot {
+                    screen: screen.clone(),
+                    path,
+                }),
+                args.json_output,
+            );
+        }
+        Commands::Next { screen } => {
+            info!("Skipping to the next wallpaper on screen {}", screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::NextWallpaper {
+                    screen: screen.clone(),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::Previous { screen } => {
+            info!("Going back to the previous wallpaper on screen {}", screen);
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::PreviousWallpaper {
+                    screen: screen.clone(),
+                }),
+                args.json_output,
+            );
+        }
+        Commands::Playlist(command) => {
+            let request = match command {
+                PlaylistCommands::Create {
+                    name,
+                    ids,
+                    interval,
+                    shuffle,
+                } => {
+                    info!("Saving playlist {} with {} wallpapers", name, ids.len());
+                    IPCRequest::SavePlaylist {
+                        playlist: Playlist {
+                            name: name.clone(),
+                            wallpapers: ids.clone(),
+                            interval_secs: *interval,
+                            order: if *shuffle {
+                                PlaylistOrder::Shuffle
+                            } else {
+                                PlaylistOrder::Sequential
+                            },
+                        },
+                    }
+                }
+                PlaylistCommands::Delete { name } => {
+                    info!("Deleting playlist {}", name);
+                    IPCRequest::DeletePlaylist { name: name.clone() }
+                }
+                PlaylistCommands::List => IPCRequest::ListPlaylists,
+                PlaylistCommands::Start { name, screen } => {
+                    info!("Starting playlist {} on screen {}", name, screen);
+                    IPCRequest::StartPlaylist {
+                        name: name.clone(),
+                        screen: screen.clone(),
+                    }
+                }
+                PlaylistCommands::Stop { screen } => {
+                    info!("Stopping playlist on screen {}", screen);
+                    IPCRequest::StopPlaylist {
+                        screen: screen.clone(),
+                    }
+                }
+            };
+
+            if !args.json_output {
+                debug!("Sending request to the daemon...");
+            }
+            handle_ipc_response(channel.send::<_, IPCResponse>(request), args.json_output);
+        }
+        Commands::Profile(command) => {
+            let request = match command {
+                ProfileCommands::Save { name } => {
+                    info!("Saving profile {}", name);
+                    IPCRequest::SaveProfile { name: name.clone() }
+                }
+                ProfileCommands::Load { name } => {
+                    info!("Loading profile {}", name);
+                    IPCRequest::LoadProfile { name: name.clone() }
+                }
+                ProfileCommands::Delete { name } => {
+                    info!("Deleting profile {}", name);
+                    IPCRequest::DeleteProfile { name: name.clone() }
+                }
+                ProfileCommands::List => IPCRequest::ListProfiles,
+            };
+
+            if !args.json_output {
+                debug!("Sending request to the daemon...");
+            }
+            handle_ipc_response(channel.send::<_, IPCResponse>(request), args.json_output);
+        }
+        Commands::Schedule(ScheduleCommands::List) => {
+            if !args.json_output {
+                debug!("Sending request to the daemon...");
+            }
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::ListSchedule),
+                args.json_output,
+            );
+        }
+        Commands::Config(_)
+        | Commands::List(_)
+        | Commands::Info { .. }
+        | Commands::Pkg(_)
+        | Commands::Tex(_) => unreachable!("Handled without connecting to the daemon"),
+        Commands::Reload => {
+            if !args.json_output {
+                debug!("Sending request to the daemon...");
+            }
+            handle_ipc_response(
+                channel.send::<_, IPCResponse>(IPCRequest::Reload),
+                args.json_output,
+            );
+        }
         Commands::KillDaemon => {
             if !args.json_output {
                 debug!("Killing the daemon...");
@@ -100,6 +612,54 @@ fn main() {
     }
 }
 
+/// The daemon doesn't share our working directory, so paths have to be made absolute
+fn absolute_path(path: &Path, json_output: bool) -> Option<PathBuf> {
+    match std::path::absolute(path) {
+        Ok(path) => Some(path),
+        Err(err) => {
+            print_cli_error(
+                CliErrorKind::InvalidArgument,
+                &format!("Invalid path {}: {}", path.to_string_lossy(), err),
+                json_output,
+            );
+            None
+        }
+    }
+}
+
+fn watch_events(socket: &str, json_output: bool) {
+    let stream = match UnixStream::connect(socket) {
+        Ok(stream) => stream,
+        Err(err) => {
+            print_daemon_connection_error(&err.to_string(), json_output);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                print_daemon_connection_error(&err.to_string(), json_output);
+                return;
+            }
+        };
+
+        if json_output {
+            println!("{}", line);
+        } else {
+            match serde_json::from_str::<IPCEvent>(&line) {
+                Ok(event) => info!("{:?}", event),
+                Err(err) => error!("Received invalid event from daemon: {}", err),
+            }
+        }
+    }
+
+    if !json_output {
+        info!("The daemon closed the event stream");
+    }
+}
+
 fn handle_ipc_response(response: Result<Option<IPCResponse>, io::Error>, json_output: bool) {
     match response {
         Ok(Some(response)) => print_ipc_response(&response, json_output),
@@ -126,37 +686,815 @@ fn print_ipc_response(response: &IPCResponse, json_output: bool) {
                 info!("Outputs: {:?}", outputs);
             }
         }
+        IPCResponse::Status(status) => {
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "outputs": status })
+                );
+            } else {
+                print_status(status);
+            }
+        }
+        IPCResponse::OutputResults(results) => {
+            if json_output {
+                let outputs: Vec<_> = results
+                    .iter()
+                    .map(|result| match &result.error {
+                        None => serde_json::json!({ "screen": result.screen, "success": true }),
+                        Some(error) => {
+                            let mut output = ipc_error_json(error);
+                            output["screen"] = result.screen.clone().into();
+                            output
+                        }
+                    })
+                    .collect();
+
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "success": results.iter().all(|result| result.error.is_none()),
+                        "outputs": outputs,
+                    })
+                );
+            } else {
+                for result in results {
+                    match &result.error {
+                        None => info!("{}: Success", result.screen),
+                        Some(error) => {
+                            error!("{}:", result.screen);
+                            print_ipc_error(error, false);
+                        }
+                    }
+                }
+            }
+        }
+        IPCResponse::Playlists(playlists) => {
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "playlists": playlists })
+                );
+            } else {
+                print_playlists(playlists);
+            }
+        }
+        IPCResponse::Profiles(profiles) => {
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "profiles": profiles })
+                );
+            } else {
+                print_profiles(profiles);
+            }
+        }
+        IPCResponse::Schedule(switches) => {
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "switches": switches })
+                );
+            } else {
+                print_schedule(switches);
+            }
+        }
+        IPCResponse::Subscribed { socket } => {
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "socket": socket })
+                );
+            } else {
+                info!("Events are available on {}", socket);
+            }
+        }
         IPCResponse::Error(error) => {
             print_ipc_error(error, json_output);
         }
     }
 }
 
-fn print_ipc_error(error: &IPCError, json_output: bool) {
-    let (error_kind, message) = match error {
-        IPCError::ScreenNotFound => ("screen_not_found", "The specified screen was not found."),
-        IPCError::WallpaperNotFound => (
-            "wallpaper_not_found",
-            "The specified wallpaper was not found.",
-        ),
-        IPCError::UnsupportedWallpaperType => (
-            "unsupported_wallpaper_type",
-            "The wallpaper type is unsupported.",
-        ),
-        IPCError::InternalError => (
-            "internal_error",
-            "An internal error occurred while processing the request.",
-        ),
-        IPCError::WallpaperLoadingError => (
-            "wallpaper_loading_error",
-            "Unable to load correctly wallpaper",
-        ),
+fn print_status(status: &[OutputStatus]) {
+    if status.is_empty() {
+        info!("No wallpaper is currently displayed");
+        return;
+    }
+
+    // Only used for the titles, the ids are enough without it
+    let library = load_library().ok();
+
+    for output in status {
+        let wallpaper = match (&output.wallpaper_id, &output.wallpaper_type) {
+            (Some(id), Some(wp_type)) => {
+                match library.as_ref().and_then(|library| library.get(*id)) {
+                    Some(entry) => format!("{} \"{}\" ({:?})", id, entry.title, wp_type),
+                    None => format!("{} ({:?})", id, wp_type),
+                }
+            }
+            (None, Some(wp_type)) => format!("unknown id ({:?})", wp_type),
+            _ => "none".to_owned(),
+        };
+
+        info!(
+            "{}: wallpaper {} - {}x{} - {} - {} FPS{}{}",
+            output.screen,
+            wallpaper,
+            output.size.0,
+            output.size.1,
+            output.scaling,
+            output.fps,
+            match output.fps_cap {
+                Some(cap) => format!(" (max {})", cap),
+                None => String::new(),
+            },
+            if output.paused { " (paused)" } else { "" }
+        );
+
+        if let Some(playlist) = &output.playlist {
+            info!("  playing playlist {}", playlist);
+        }
+    }
+}
+
+fn print_playlists(playlists: &[Playlist]) {
+    if playlists.is_empty() {
+        info!("No playlist saved");
+        return;
+    }
+
+    for playlist in playlists {
+        info!(
+            "{}: {} wallpapers every {}s ({:?}) - {:?}",
+            playlist.name,
+            playlist.wallpapers.len(),
+            playlist.interval_secs,
+            playlist.order,
+            playlist.wallpapers
+        );
+    }
+}
+
+fn print_profiles(profiles: &[Profile]) {
+    if profiles.is_empty() {
+        info!("No profile saved");
+        return;
+    }
+
+    for profile in profiles {
+        info!("{}:", profile.name);
+
+        for output in &profile.outputs {
+            let wallpaper = match &output.wallpaper {
+                Some(wallpaper) => wallpaper.to_string(),
+                None => "none".to_owned(),
+            };
+            let scaling = match output.scaling {
+                Some(scaling) => scaling.to_string(),
+                None => "default scaling".to_owned(),
+            };
+
+            info!(
+                "  {}: wallpaper {} - {}{}{}",
+                output.screen,
+                wallpaper,
+                scaling,
+                match output.fps_cap {
+                    Some(cap) => format!(" - max {} FPS", cap),
+                    None => String::new(),
+                },
+                match output.properties.len() {
+                    0 => String::new(),
+                    count => format!(" - {} properties", count),
+                }
+            );
+        }
+    }
+}
+
+fn print_schedule(switches: &[ScheduledSwitch]) {
+    if switches.is_empty() {
+        info!("No wallpaper switch planned in the next 24 hours");
+        return;
+    }
+
+    for switch in switches {
+        let time = match DateTime::from_timestamp(switch.timestamp, 0) {
+            Some(time) => time.with_timezone(&Local).format("%a %H:%M").to_string(),
+            None => switch.timestamp.to_string(),
+        };
+
+        info!(
+            "{} - {}: wallpaper {} ({})",
+            time, switch.screen, switch.wallpaper_id, switch.rule
+        );
+    }
+}
+
+/// Indexes the wallpapers of the workshop directories the daemon uses
+fn load_library() -> Result<Library, (CliErrorKind, String)> {
+    let config = Config::load(None).unwrap_or_default();
+    let wpe_dirs = waypaper_engine_shared::get_wpe_dirs(&config.workshop_dirs)
+        .map_err(|err| (CliErrorKind::NotFound, err.to_string()))?;
+
+    Library::load(&wpe_dirs).map_err(|err| {
+        (
+            io_error_kind(&err),
+            format!("Unable to scan the wallpapers: {}", err),
+        )
+    })
+}
+
+fn list_wallpapers(args: &ListArgs, json_output: bool) {
+    let library = match load_library() {
+        Ok(library) => library,
+        Err((kind, err)) => {
+            print_cli_error(kind, &err, json_output);
+            std::process::exit(1);
+        }
+    };
+
+    let title = args.title.as_ref().map(|title| title.to_lowercase());
+    let mut wallpapers: Vec<&LibraryEntry> = library
+        .wallpapers()
+        .filter(|entry| {
+            args.wallpaper_type
+                .as_ref()
+                .is_none_or(|wp_type| entry.wallpaper_type == *wp_type)
+        })
+        .filter(|entry| {
+            args.tags
+                .iter()
+                .all(|tag| entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        })
+        .filter(|entry| {
+            args.rating.as_ref().is_none_or(|rating| {
+                entry
+                    .content_rating
+                    .as_ref()
+                    .is_some_and(|r| r.eq_ignore_ascii_case(rating))
+            })
+        })
+        .filter(|entry| {
+            title
+                .as_ref()
+                .is_none_or(|title| entry.title.to_lowercase().contains(title))
+        })
+        .collect();
+
+    // The library is sorted by id, which the other orders keep for equal values
+    match args.sort {
+        SortKey::Id => {}
+        SortKey::Title => wallpapers.sort_by_key(|entry| entry.title.to_lowercase()),
+        SortKey::Type => wallpapers.sort_by_key(|entry| entry.wallpaper_type.name()),
+        SortKey::Size => wallpapers.sort_by_key(|entry| entry.size),
+        SortKey::Modified => wallpapers.sort_by_key(|entry| entry.modified),
+    }
+    if args.reverse {
+        wallpapers.reverse();
+    }
+
+    if json_output {
+        println!(
+            "{}",
+            serde_json::json!({ "success": true, "wallpapers": wallpapers })
+        );
+        return;
+    }
+
+    if wallpapers.is_empty() {
+        info!("No wallpaper found");
+        return;
+    }
+
+    for entry in &wallpapers {
+        info!(
+            "{} - {} - \"{}\"{}{}",
+            entry.id,
+            entry.wallpaper_type,
+            entry.title,
+            match &entry.content_rating {
+                Some(rating) => format!(" ({})", rating),
+                None => String::new(),
+            },
+            if entry.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", entry.tags.join(", "))
+            }
+        );
+    }
+    info!("{} wallpapers", wallpapers.len());
+}
+
+fn print_wallpaper_info(id: u64, json_output: bool) {
+    let library = match load_library() {
+        Ok(library) => library,
+        Err((kind, err)) => {
+            print_cli_error(kind, &err, json_output);
+            std::process::exit(1);
+        }
+    };
+
+    let Some(entry) = library.get(id) else {
+        print_cli_error(
+            CliErrorKind::NotFound,
+            &format!("No installed wallpaper has the id {}", id),
+            json_output,
+        );
+        std::process::exit(1);
+    };
+
+    let project = match WEProject::new(&entry.path.join("project.json"), Some(id)) {
+        Ok(project) => project,
+        Err(err) => {
+            print_cli_error(
+                io_error_kind(&err),
+                &format!("Unable to read the wallpaper project: {}", err),
+                json_output,
+            );
+            std::process::exit(1);
+        }
+    };
+
+    if json_output {
+        println!(
+            "{}",
+            serde_json::json!({
+                "success": true,
+                "path": entry.path,
+                "size": entry.size,
+                "project": project,
+            })
+        );
+        return;
+    }
+
+    info!("{} - \"{}\"", id, project.title);
+    info!("  type: {}", project.wallpaper_type);
+    info!("  path: {}", entry.path.to_string_lossy());
+    info!("  size: {:.1} MiB", entry.size as f64 / (1024.0 * 1024.0));
+    if let Some(rating) = &project.content_rating {
+        info!("  content rating: {}", rating);
+    }
+    if let Some(tags) = project.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        info!("  tags: {}", tags.join(", "));
+    }
+    if let Some(file) = &project.file {
+        info!("  file: {}", file);
+    }
+    if let Some(url) = &project.workshop_url {
+        info!("  workshop: {}", url);
+    }
+    if let Some(description) = project
+        .description
+        .as_ref()
+        .filter(|description| !description.is_empty())
+    {
+        info!("  description: {}", description.replace('\n', " "));
+    }
+
+    let Some(general) = project
+        .general
+        .as_ref()
+        .filter(|general| !general.properties.is_empty())
+    else {
+        return;
+    };
+
+    let mut properties: Vec<_> = general.properties.iter().collect();
+    properties.sort_by(|a, b| a.1.order.cmp(&b.1.order).then_with(|| a.0.cmp(b.0)));
+
+    info!("  properties:");
+    for (key, property) in properties {
+        match property_value(&property.value) {
+            Some(value) => info!("    {} = {} ({})", key, value, property.text),
+            // Text properties are only labels
+            None => info!("    {} ({})", key, property.text),
+        }
+    }
+}
+
+/// Current value of a property, as accepted by `set-property`
+fn property_value(value: &PropertyValue) -> Option<String> {
+    Some(match value {
+        PropertyValue::Color { r, g, b } => format!("{} {} {}", r, g, b),
+        PropertyValue::Slider {
+            min, max, value, ..
+        } => format!("{} [{}, {}]", value, min, max),
+        PropertyValue::Combo { options, value } => {
+            let current = options
+                .iter()
+                .find(|option| option.value.as_deref() == Some(value.to_string().as_str()))
+                .map_or_else(|| value.to_string(), |option| option.label.clone());
+            let labels: Vec<_> = options.iter().map(|option| option.label.as_str()).collect();
+            format!("{} [{}]", current, labels.join(", "))
+        }
+        PropertyValue::Bool { value } => value.to_string(),
+        PropertyValue::TextInput { value } => format!("\"{}\"", value),
+        PropertyValue::File { value } => value.clone().unwrap_or_else(|| "none".to_owned()),
+        PropertyValue::Directory { mode } => format!("directory ({:?})", mode),
+        PropertyValue::Text {} => return None,
+    })
+}
+
+fn run_pkg_command(command: &PkgCommands, json_output: bool) {
+    let path = match command {
+        PkgCommands::List { path } | PkgCommands::Extract { path, .. } => path,
+    };
+
+    let package = match ScenePackage::new(path) {
+        Ok(package) => package,
+        Err(err) => {
+            // Missing packages are refused before anything is read
+            let kind = if path.exists() {
+                error_kind(err.chain())
+            } else {
+                CliErrorKind::NotFound
+            };
+            print_cli_error(
+                kind,
+                &format!("Unable to read the package: {:#}", err),
+                json_output,
+            );
+            std::process::exit(1);
+        }
+    };
+
+    match command {
+        PkgCommands::List { .. } => {
+            if json_output {
+                let files: Vec<_> = package
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        serde_json::json!({
+                            "name": entry.name,
+                            "offset": entry.offset,
+                            "size": entry.size,
+                        })
+                    })
+                    .collect();
+
+                println!("{}", serde_json::json!({ "success": true, "files": files }));
+                return;
+            }
+
+            for entry in &package.entries {
+                info!(
+                    "{} - offset {} - {}",
+                    entry.name,
+                    entry.offset,
+                    entry.formatted_size()
+                );
+            }
+            info!("{} files", package.entries.len());
+        }
+        PkgCommands::Extract { dir, .. } => {
+            if let Err(err) = package.save_to_disk(dir) {
+                print_cli_error(
+                    error_kind(err.chain()),
+                    &format!("Unable to extract the package: {:#}", err),
+                    json_output,
+                );
+                std::process::exit(1);
+            }
+
+            if json_output {
+                print_json_success();
+            } else {
+                info!(
+                    "Extracted {} files to {}",
+                    package.contents.len(),
+                    dir.to_string_lossy()
+                );
+            }
+        }
+    }
+}
+
+fn run_tex_command(command: &TexCommands, json_output: bool) {
+    let path = match command {
+        TexCommands::Info { path } | TexCommands::Convert { path, .. } => path,
+    };
+
+    let texture = match TexFile::from_path(path) {
+        Ok(texture) => texture,
+        Err(err) => {
+            print_cli_error(
+                error_kind(err.chain()),
+                &format!("Unable to read the texture: {:#}", err),
+                json_output,
+            );
+            std::process::exit(1);
+        }
+    };
+
+    match command {
+        TexCommands::Info { .. } => print_tex_info(&texture, json_output),
+        TexCommands::Convert {
+            output, all_frames, ..
+        } => {
+            let written = if *all_frames {
+                convert_tex_frames(&texture, output)
+            } else {
+                texture
+                    .to_rgba(0, 0)
+                    .map_err(|err| (error_kind(err.chain()), format!("{:#}", err)))
+                    .and_then(|image| image.save(output).map_err(image_save_error))
+                    .map(|()| vec![output.clone()])
+            };
+
+            match written {
+                Ok(files) if json_output => {
+                    println!("{}", serde_json::json!({ "success": true, "files": files }))
+                }
+                Ok(files) => {
+                    for file in files {
+                        info!("Wrote {}", file.to_string_lossy());
+                    }
+                }
+                Err((kind, err)) => {
+                    print_cli_error(
+                        kind,
+                        &format!("Unable to convert the texture: {}", err),
+                        json_output,
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Writes each spritesheet frame to `<output stem>_<frame>.<output extension>`
+fn convert_tex_frames(
+    texture: &TexFile,
+    output: &Path,
+) -> Result<Vec<PathBuf>, (CliErrorKind, String)> {
+    let Some(frames) = &texture.frames_infos else {
+        return Err((
+            CliErrorKind::InvalidArgument,
+            "The texture is not a spritesheet".to_owned(),
+        ));
+    };
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut files = vec![];
+
+    for (i, frame) in frames.frame_infos.iter().enumerate() {
+        let mut file = output.with_file_name(format!("{}_{}", stem, i));
+        if let Some(extension) = output.extension() {
+            file.set_extension(extension);
+        }
+
+        texture
+            .frame_to_rgba(frame)
+            .map_err(|err| (error_kind(err.chain()), format!("{:#}", err)))
+            .and_then(|image| image.save(&file).map_err(image_save_error))
+            .map_err(|(kind, err)| (kind, format!("frame {}: {}", i, err)))?;
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+fn print_tex_info(texture: &TexFile, json_output: bool) {
+    let header = &texture.header;
+    let container = &texture.container;
+    let flags: Vec<_> = header
+        .texture_flags
+        .iter_names()
+        .map(|(name, _)| name)
+        .collect();
+    let (r, g, b, a) = header.dominant_color;
+
+    if json_output {
+        let images: Vec<_> = texture
+            .images
+            .iter()
+            .map(|mipmaps| {
+                mipmaps
+                    .iter()
+                    .map(|mipmap| {
+                        serde_json::json!({
+                            "width": mipmap.width,
+                            "height": mipmap.height,
+                            "size": mipmap.bytes.len(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let frames = texture.frames_infos.as_ref().map(|frames| {
+            let frame_infos: Vec<_> = frames
+                .frame_infos
+                .iter()
+                .map(|frame| {
+                    serde_json::json!({
+                        "image": frame.image_id,
+                        "frame_time": frame.frame_time,
+                        "x": frame.x,
+                        "y": frame.y,
+                        "width": frame.width,
+                        "height": frame.height,
+                    })
+                })
+                .collect();
+
+            serde_json::json!({
+                "version": format!("{:?}", frames.version),
+                "sprite_size": frames.sprite_size.map(|size| [size.x, size.y]),
+                "frames": frame_infos,
+            })
+        });
+
+        println!(
+            "{}",
+            serde_json::json!({
+                "success": true,
+                "format": format!("{:?}", header.format),
+                "flags": flags,
+                "texture_width": header.texture_width,
+                "texture_height": header.texture_height,
+                "image_width": header.image_width,
+                "image_height": header.image_height,
+                "dominant_color": [r, g, b, a],
+                "container_version": format!("{:?}", container.version),
+                "freeimage_format": container
+                    .freeimage_format
+                    .map(|format| format!("{:?}", format)),
+                "images": images,
+                "spritesheet": frames,
+            })
+        );
+        return;
+    }
+
+    info!("format: {:?}", header.format);
+    if !flags.is_empty() {
+        info!("flags: {}", flags.join(", "));
+    }
+    info!(
+        "texture size: {}x{}",
+        header.texture_width, header.texture_height
+    );
+    info!("image size: {}x{}", header.image_width, header.image_height);
+    info!("dominant color: {} {} {} {}", r, g, b, a);
+    info!("container version: {:?}", container.version);
+    match container.freeimage_format {
+        Some(format) => info!("FreeImage format: {:?}", format),
+        None => info!("FreeImage format: none (raw pixels)"),
+    }
+
+    for (i, mipmaps) in texture.images.iter().enumerate() {
+        info!("image {}: {} mipmaps", i, mipmaps.len());
+        for (level, mipmap) in mipmaps.iter().enumerate() {
+            info!(
+                "  {}: {}x{} - {} bytes",
+                level,
+                mipmap.width,
+                mipmap.height,
+                mipmap.bytes.len()
+            );
+        }
+    }
+
+    let Some(frames) = &texture.frames_infos else {
+        return;
     };
 
+    info!(
+        "spritesheet: {} frames ({:?})",
+        frames.frame_infos.len(),
+        frames.version
+    );
+    if let Some(size) = frames.sprite_size {
+        info!("  sprite size: {}x{}", size.x, size.y);
+    }
+    for (i, frame) in frames.frame_infos.iter().enumerate() {
+        info!(
+            "  {}: image {} at {}x{}, {}x{}, {}s",
+            i, frame.image_id, frame.x, frame.y, frame.width, frame.height, frame.frame_time
+        );
+    }
+}
+
+fn check_config(path: Option<&Path>, json_output: bool) {
+    let path = path.map_or_else(config_path, Path::to_path_buf);
+
+    match Config::read(&path) {
+        Ok(_) => {
+            if json_output {
+                print_json_success();
+            } else {
+                info!("{} is valid", path.to_string_lossy());
+            }
+        }
+        Err(err) if json_output => {
+            let (line, column, key) = match &err {
+                ConfigError::Invalid { position, key, .. } => {
+                    (position.map(|p| p.0), position.map(|p| p.1), key.clone())
+                }
+                ConfigError::Io { .. } => (None, None, None),
+            };
+
+            println!(
+                "{}",
+                serde_json::json!({
+                    "success": false,
+                    "error": "invalid_config",
+                    "message": err.to_string(),
+                    "line": line,
+                    "column": column,
+                    "key": key,
+                })
+            );
+            std::process::exit(1);
+        }
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_ipc_error(error: &IPCError, json_output: bool) {
+    if json_output {
+        println!("{}", ipc_error_json(error));
+    } else {
+        match &error.subject {
+            Some(subject) => error!("Error: {} ({})", error.message, subject),
+            None => error!("Error: {}", error.message),
+        }
+
+        for cause in &error.causes {
+            error!("  Caused by: {}", cause);
+        }
+    }
+}
+
+fn ipc_error_json(error: &IPCError) -> serde_json::Value {
+    serde_json::json!({
+        "success": false,
+        "error": error.kind,
+        "message": error.message,
+        "subject": error.subject,
+        "causes": error.causes,
+    })
+}
+
+/// Errors of the commands which don't involve the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CliErrorKind {
+    InvalidArgument,
+    NotFound,
+    IoError,
+    InvalidData,
+}
+
+impl CliErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CliErrorKind::InvalidArgument => "invalid_argument",
+            CliErrorKind::NotFound => "not_found",
+            CliErrorKind::IoError => "io_error",
+            CliErrorKind::InvalidData => "invalid_data",
+        }
+    }
+}
+
+fn io_error_kind(error: &io::Error) -> CliErrorKind {
+    match error.kind() {
+        io::ErrorKind::NotFound => CliErrorKind::NotFound,
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => CliErrorKind::InvalidData,
+        _ => CliErrorKind::IoError,
+    }
+}
+
+/// Kind of an error from its chain of causes: the I/O error among them if any, otherwise the
+/// file content was at fault
+fn error_kind<'a>(
+    mut causes: impl Iterator<Item = &'a (dyn std::error::Error + 'static)>,
+) -> CliErrorKind {
+    causes
+        .find_map(|cause| cause.downcast_ref::<io::Error>())
+        .map_or(CliErrorKind::InvalidData, io_error_kind)
+}
+
+/// Failing to write an image is reported as an I/O error, whatever the encoder says
+fn image_save_error(error: impl std::fmt::Display) -> (CliErrorKind, String) {
+    (CliErrorKind::IoError, error.to_string())
+}
+
+fn print_cli_error(kind: CliErrorKind, message: &str, json_output: bool) {
     if json_output {
-        print_json_error(error_kind, message);
+        println!(
+            "{}",
+            serde_json::json!({ "success": false, "error": kind.as_str(), "message": message })
+        );
     } else {
-        error!("Error: {}", message);
+        error!("{}", message);
     }
 }
 
@@ -185,10 +1523,3 @@ fn print_daemon_connection_error(error: &str, json_output: bool) {
 fn print_json_success() {
     println!(r#"{{"success": true}}"#);
 }
-
-fn print_json_error(error_kind: &str, message: &str) {
-    println!(
-        r#"{{"success": false, "error": "{}", "message": "{}"}}"#,
-        error_kind, message
-    );
-}
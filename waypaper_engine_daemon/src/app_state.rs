@@ -1,47 +1,127 @@
-use crate::profile_manager::ProfileManager;
+use crate::event_broadcaster::EventBroadcaster;
+use crate::file_watcher::FileWatcher;
+use crate::instance_lock::InstanceLock;
+use crate::playlist_manager::{PlaylistManager, validate_playlist};
+use crate::power::PowerMonitor;
+use crate::profile_manager::{self, OutputChange, ProfileManager};
+use crate::scheduler::Scheduler;
+use crate::transition::Transition;
 use crate::wallpaper::Wallpaper;
 use crate::wl_renderer::RenderingContext;
+use anyhow::Context;
 use crossbeam::channel::{Receiver, Sender, TryRecvError};
+use glob::Pattern;
+use image::ImageFormat;
 use linux_ipc::IpcChannel;
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::thread;
-use waypaper_engine_shared::ipc::{IPCError, IPCRequest, IPCResponse, InternalRequest};
+use std::time::Instant;
+use waypaper_engine_shared::config::{Config, config_path};
+use waypaper_engine_shared::get_events_socket_path;
+use waypaper_engine_shared::ipc::{
+    IPCError, IPCErrorKind, IPCEvent, IPCRequest, IPCResponse, InternalRequest, OutputResult,
+    Playlist, WallpaperRef,
+};
+use waypaper_engine_shared::library::Library;
 
 pub struct AppState {
-    wpe_dir: PathBuf,
+    library: Library,
+    socket_path: PathBuf,
     rendering_context: RenderingContext,
     internal_ipc_tx: Sender<(InternalRequest, Sender<IPCResponse>)>,
     internal_ipc_rx: Receiver<(InternalRequest, Sender<IPCResponse>)>,
     profile_manager: ProfileManager,
+    playlist_manager: PlaylistManager,
+    scheduler: Scheduler,
+    power_monitor: PowerMonitor,
+    config: Config,
+    /// The configuration file given on the command line, the default one otherwise
+    config_file: Option<PathBuf>,
+    file_watcher: Option<FileWatcher>,
+    events: EventBroadcaster,
 }
 
 impl AppState {
-    pub fn new(wpe_dir: PathBuf) -> Self {
-        tracing::debug!(
-            "Using wallpaper engine workshop path {}",
-            wpe_dir.to_string_lossy()
-        );
+    pub fn new(
+        wpe_dirs: Vec<PathBuf>,
+        socket_path: PathBuf,
+        config_file: Option<PathBuf>,
+        config: Config,
+    ) -> Self {
+        for wpe_dir in &wpe_dirs {
+            tracing::debug!(
+                "Using wallpaper engine workshop path {}",
+                wpe_dir.to_string_lossy()
+            );
+        }
 
         let (internal_ipc_tx, internal_ipc_rx) =
             crossbeam::channel::unbounded::<(InternalRequest, Sender<IPCResponse>)>();
 
+        let mut library = Library::open(&wpe_dirs);
+        if let Err(err) = library.rescan() {
+            tracing::warn!("Unable to scan the wallpaper library: {}", err);
+        }
+
+        let events = EventBroadcaster::new();
+
+        let mut rendering_context = RenderingContext::new(internal_ipc_tx.clone(), events.clone());
+        rendering_context.set_transition(Transition::from_config(&config.transition));
+        rendering_context.set_video_config(config.video);
+        rendering_context.set_fps_cap(None, config.fps_cap.map(NonZeroU32::get));
+
+        let watched_config = config_file.clone().unwrap_or_else(config_path);
+        let file_watcher = match FileWatcher::new(&[watched_config, profile_manager::save_path()]) {
+            Ok(file_watcher) => Some(file_watcher),
+            Err(err) => {
+                tracing::warn!("Unable to watch the configuration files: {}", err);
+                None
+            }
+        };
+
         AppState {
-            wpe_dir,
-            rendering_context: RenderingContext::new(internal_ipc_tx.clone()),
+            library,
+            socket_path,
+            rendering_context,
             internal_ipc_tx,
             internal_ipc_rx,
             profile_manager: ProfileManager::new(),
+            playlist_manager: PlaylistManager::new(),
+            scheduler: Scheduler::new(),
+            power_monitor: PowerMonitor::new(config.power.clone()),
+            config,
+            config_file,
+            file_watcher,
+            events,
         }
     }
 
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let _instance_lock = InstanceLock::acquire(&self.socket_path)?;
+        tracing::debug!(
+            "Using IPC socket path {}",
+            self.socket_path.to_string_lossy()
+        );
+
         ffmpeg_next::init()?;
 
+        let events_socket_path = get_events_socket_path(&self.socket_path);
+        self.events.listen(&events_socket_path)?;
+
         let internal_ipc_tx = self.internal_ipc_tx.clone();
+        let socket_path = self.socket_path.clone();
 
         let ipc_thread = thread::spawn(move || {
-            let mut channel = IpcChannel::new("/tmp/waypaper-engine.sock").unwrap();
+            let mut channel = IpcChannel::new(
+                socket_path
+                    .to_str()
+                    .expect("IPC socket path is not valid UTF-8"),
+            )
+            .unwrap();
             tracing::info!("Started IPC channel");
 
             loop {
@@ -66,8 +146,12 @@ impl AppState {
                             }
                             Err(err) => {
                                 tracing::warn!("Failed to compute IPC response: {}", err);
-                                if let Err(err) = reply(IPCResponse::Error(IPCError::InternalError))
-                                {
+                                let error = IPCError::new(
+                                    IPCErrorKind::InternalError,
+                                    "An internal error occurred while processing the request",
+                                )
+                                .with_causes(vec![err.to_string()]);
+                                if let Err(err) = reply(IPCResponse::Error(error)) {
                                     tracing::warn!("Failed to send IPC error response: {}", err);
                                 }
                             }
@@ -80,13 +164,25 @@ impl AppState {
 
         loop {
             self.rendering_context.tick();
+            self.advance_playlists();
+            self.run_schedule();
+            self.reload_changed_files();
+
+            if let Some(power_saving) = self.power_monitor.poll() {
+                self.rendering_context.set_power_saving(power_saving);
+            }
 
             match self.internal_ipc_rx.try_recv() {
                 Ok((req, response)) => match req {
                     InternalRequest::SetWallpaper { id, screen } => {
-                        if Self::set_wallpaper(self, id, &screen, &response) {
-                            self.profile_manager.save_wallpaper(id, &screen);
-                        }
+                        response.send(
+                            self.set_wallpaper_on_outputs(&WallpaperRef::Workshop(id), &screen),
+                        )?;
+                    }
+                    InternalRequest::SetWallpaperPath { path, screen } => {
+                        response.send(
+                            self.set_wallpaper_on_outputs(&WallpaperRef::Path(path), &screen),
+                        )?;
                     }
 
                     InternalRequest::ListOutputs => {
@@ -98,15 +194,147 @@ impl AppState {
                             .collect();
                         response.send(IPCResponse::Outputs(outputs))?;
                     }
+                    InternalRequest::GetStatus => {
+                        let mut status = self.rendering_context.get_status();
+                        for output in &mut status {
+                            output.playlist = self
+                                .playlist_manager
+                                .running_playlist(&output.screen)
+                                .map(ToOwned::to_owned);
+                        }
+                        response.send(IPCResponse::Status(status))?;
+                    }
+                    InternalRequest::Subscribe => {
+                        response.send(IPCResponse::Subscribed {
+                            socket: events_socket_path.to_string_lossy().into_owned(),
+                        })?;
+                    }
+                    InternalRequest::Pause { screen } => {
+                        response.send(self.set_paused(&screen, Some(true)))?;
+                    }
+                    InternalRequest::Resume { screen } => {
+                        response.send(self.set_paused(&screen, Some(false)))?;
+                    }
+                    InternalRequest::TogglePause { screen } => {
+                        response.send(self.set_paused(&screen, None))?;
+                    }
+                    InternalRequest::SetScaling { screen, mode } => {
+                        let response_msg = if self.rendering_context.set_scaling(&screen, mode) {
+                            tracing::info!("Set scaling [{}] on output [{}]", mode, screen);
+                            match self.profile_manager.save_scaling(&screen, mode) {
+                                Ok(()) => IPCResponse::Success,
+                                Err(err) => IPCResponse::Error(profiles_write_error(err)),
+                            }
+                        } else {
+                            IPCResponse::Error(no_wallpaper_displayed(&screen))
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::SetFpsCap { screen, fps } => {
+                        response.send(self.set_fps_cap(screen.as_deref(), fps))?;
+                    }
+                    InternalRequest::SetProperty { screen, key, value } => {
+                        let response_msg = match self.set_property(&screen, &key, &value) {
+                            Ok(()) => {
+                                match self.profile_manager.save_property(&screen, &key, &value) {
+                                    Ok(()) => IPCResponse::Success,
+                                    Err(err) => IPCResponse::Error(profiles_write_error(err)),
+                                }
+                            }
+                            Err(error) => IPCResponse::Error(error),
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::Screenshot { screen, path } => {
+                        let response_msg = match self.screenshot(&screen, &path) {
+                            Ok(()) => IPCResponse::Success,
+                            Err(error) => IPCResponse::Error(error),
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::SavePlaylist { playlist } => {
+                        response.send(self.save_playlist(playlist))?;
+                    }
+                    InternalRequest::DeletePlaylist { name } => {
+                        let response_msg = match self.playlist_manager.delete_playlist(&name) {
+                            Ok(true) => {
+                                tracing::info!("Deleted playlist [{}]", name);
+                                IPCResponse::Success
+                            }
+                            Ok(false) => IPCResponse::Error(playlist_not_found(&name)),
+                            Err(err) => IPCResponse::Error(playlists_write_error(err)),
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::ListPlaylists => {
+                        response.send(IPCResponse::Playlists(self.playlist_manager.playlists()))?;
+                    }
+                    InternalRequest::StartPlaylist { name, screen } => {
+                        response.send(self.start_playlist(&name, &screen))?;
+                    }
+                    InternalRequest::StopPlaylist { screen } => {
+                        let response_msg = match self.playlist_manager.stop(&screen) {
+                            Ok(true) => {
+                                tracing::info!("Stopped playlist on output [{}]", screen);
+                                IPCResponse::Success
+                            }
+                            Ok(false) => IPCResponse::Error(no_playlist_running(&screen)),
+                            Err(err) => IPCResponse::Error(playlists_write_error(err)),
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::NextWallpaper { screen } => {
+                        response.send(self.step_playlist(&screen, true))?;
+                    }
+                    InternalRequest::PreviousWallpaper { screen } => {
+                        response.send(self.step_playlist(&screen, false))?;
+                    }
+                    InternalRequest::ListSchedule => {
+                        response.send(IPCResponse::Schedule(self.scheduler.upcoming()))?;
+                    }
+                    InternalRequest::Reload => {
+                        response.send(self.reload())?;
+                    }
+                    InternalRequest::SaveProfile { name } => {
+                        response.send(self.save_profile(&name))?;
+                    }
+                    InternalRequest::LoadProfile { name } => {
+                        response.send(self.load_profile(&name))?;
+                    }
+                    InternalRequest::DeleteProfile { name } => {
+                        let response_msg = match self.profile_manager.delete_profile(&name) {
+                            Ok(true) => {
+                                tracing::info!("Deleted profile [{}]", name);
+                                IPCResponse::Success
+                            }
+                            Ok(false) => IPCResponse::Error(profile_not_found(&name)),
+                            Err(err) => IPCResponse::Error(profiles_write_error(err)),
+                        };
+                        response.send(response_msg)?;
+                    }
+                    InternalRequest::ListProfiles => {
+                        response.send(IPCResponse::Profiles(self.profile_manager.profiles()))?;
+                    }
                     InternalRequest::KillDaemon => {
                         unreachable!()
                     }
-                    InternalRequest::NewOutput { screen } => {
-                        if let Some(id) = self.profile_manager.load_wallpaper(&screen)
-                            && Self::set_wallpaper(self, id, &screen, &response)
+                    InternalRequest::NewOutput { screen, monitor } => {
+                        if let Some(monitor) = &monitor {
+                            tracing::debug!("Output [{}] is monitor [{}]", screen, monitor);
+                        }
+                        self.profile_manager.register_output(&screen, monitor);
+
+                        if let Some(wallpaper) = self.profile_manager.load_wallpaper(&screen)
+                            && self.set_wallpaper(&wallpaper, &screen).is_ok()
                         {
-                            tracing::info!("Wallpaper [{}] loaded for screen [{}]", id, screen);
+                            tracing::info!(
+                                "Wallpaper [{}] loaded for screen [{}]",
+                                wallpaper,
+                                screen
+                            );
+                            self.restore_output_settings(&screen);
                         }
+                        self.scheduler.invalidate();
                     }
                 },
                 Err(err) => match err {
@@ -118,118 +346,772 @@ impl AppState {
 
         ipc_thread.join().unwrap();
 
+        for path in [&self.socket_path, &events_socket_path] {
+            if let Err(err) = fs::remove_file(path) {
+                tracing::debug!(
+                    "Unable to remove socket {}: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+            }
+        }
+
         tracing::info!("Daemon stopped");
 
         Ok(())
     }
 
-    fn set_wallpaper(&mut self, id: u64, screen: &str, response: &Sender<IPCResponse>) -> bool {
-        let outputs = self.rendering_context.get_outputs();
+    /// Resolves an output name, `*` or a glob pattern into the names of the matching outputs
+    fn matching_outputs(&mut self, pattern: &str) -> Result<Vec<String>, IPCError> {
+        let mut outputs: Vec<String> = self
+            .rendering_context
+            .get_outputs()
+            .drain()
+            .filter_map(|(_, output)| output.name)
+            .collect();
+        outputs.sort();
 
-        if let Some(output) = outputs.iter().find(|output| match output.1.name.as_ref() {
-            Some(name) => name == screen,
-            None => false,
-        }) {
-            let path = self.wpe_dir.join(id.to_string());
-
-            if !path.exists() {
-                tracing::warn!("Wallpaper path does not exist: {:?}", path);
-                response
-                    .send(IPCResponse::Error(IPCError::WallpaperNotFound))
-                    .unwrap();
-                return false;
-            }
-
-            if !path.is_dir() {
-                tracing::warn!("Wallpaper path is not a directory: {:?}", path);
-                response
-                    .send(IPCResponse::Error(IPCError::WallpaperNotFound))
-                    .unwrap();
-                // The wallpaper path is expected to be a directory containing wallpaper resources.
-                return false;
-            }
-
-            let wallpaper = match Wallpaper::new(path.clone()) {
-                Ok(wallpaper) => wallpaper,
-                Err(e) => {
-                    tracing::warn!("Failed to load wallpaper: {:?}", e);
-                    response
-                        .send(IPCResponse::Error(IPCError::WallpaperLoadingError))
-                        .unwrap();
-                    return false;
+        // An exact name always wins, so that outputs containing glob characters can be targeted
+        if outputs.iter().any(|output| output == pattern) {
+            return Ok(vec![pattern.to_owned()]);
+        }
+
+        let glob = Pattern::new(pattern).map_err(|err| {
+            IPCError::new(IPCErrorKind::InvalidArgument, "Invalid screen pattern")
+                .with_subject(pattern)
+                .with_causes(vec![err.to_string()])
+        })?;
+
+        outputs.retain(|output| glob.matches(output));
+
+        if outputs.is_empty() {
+            tracing::warn!("No output matches the requested screen: [{}]", pattern);
+            return Err(IPCError::new(
+                IPCErrorKind::ScreenNotFound,
+                "The specified screen was not found",
+            )
+            .with_subject(pattern));
+        }
+
+        Ok(outputs)
+    }
+
+    fn set_wallpaper_on_outputs(&mut self, wallpaper: &WallpaperRef, pattern: &str) -> IPCResponse {
+        let screens = match self.matching_outputs(pattern) {
+            Ok(screens) => screens,
+            Err(error) => return IPCResponse::Error(error),
+        };
+
+        let results = screens
+            .into_iter()
+            .map(|screen| {
+                let result = self.apply_wallpaper(wallpaper, &screen);
+                // Picking a wallpaper by hand takes the output out of its playlist
+                if result.is_ok() {
+                    self.stop_replaced_playlist(&screen);
                 }
-            };
-            match wallpaper {
-                Wallpaper::Video { ref project, .. } => {
-                    if let Some(file) = project.file.as_ref() {
-                        let video_path = path.join(file);
-                        if video_path.exists() {
-                            tracing::info!("Found video file ! (Path : {video_path:?})");
-                            self.rendering_context.set_wallpaper(output, wallpaper);
-                        } else {
-                            tracing::warn!("Video file does not exist: {:?}", video_path);
-                            response
-                                .send(IPCResponse::Error(IPCError::WallpaperNotFound))
-                                .unwrap();
-                            return false;
-                        }
-                    } else {
-                        tracing::warn!("Wallpaper project file is None for video wallpaper");
-                        response
-                            .send(IPCResponse::Error(IPCError::WallpaperNotFound))
-                            .unwrap();
-                        return false;
-                    }
+
+                OutputResult {
+                    screen,
+                    error: result.and_then(saved).err(),
                 }
-                Wallpaper::Scene { .. } => {
-                    let scene_pkg_file = path.join("scene.pkg");
+            })
+            .collect();
 
-                    if scene_pkg_file.exists() {
-                        tracing::info!("Found scene package file ! (Path : {scene_pkg_file:?})");
+        IPCResponse::OutputResults(results)
+    }
 
-                        self.rendering_context.set_wallpaper(output, wallpaper);
-                    }
+    /// Sets the wallpaper, then remembers it and restores the settings saved for the output.
+    /// Returns an error if the wallpaper couldn't be displayed, otherwise the error saving it if
+    /// any, see [`saved`].
+    fn apply_wallpaper(
+        &mut self,
+        wallpaper: &WallpaperRef,
+        screen: &str,
+    ) -> Result<Option<IPCError>, IPCError> {
+        self.set_wallpaper(wallpaper, screen)?;
+        let save_result = self.profile_manager.save_wallpaper(wallpaper, screen);
+        self.restore_output_settings(screen);
+
+        Ok(save_result.err().map(profiles_write_error))
+    }
+
+    fn save_playlist(&mut self, playlist: Playlist) -> IPCResponse {
+        if let Err(reason) = validate_playlist(&playlist) {
+            let error = IPCError::new(IPCErrorKind::InvalidArgument, reason);
+            return IPCResponse::Error(if playlist.name.is_empty() {
+                error
+            } else {
+                error.with_subject(&playlist.name)
+            });
+        }
+
+        let name = playlist.name.clone();
+        let count = playlist.wallpapers.len();
+        if let Err(err) = self.playlist_manager.save_playlist(playlist) {
+            return IPCResponse::Error(playlists_write_error(err));
+        }
+
+        tracing::info!("Saved playlist [{}] with {} wallpapers", name, count);
+
+        IPCResponse::Success
+    }
+
+    fn save_profile(&mut self, name: &str) -> IPCResponse {
+        if name.is_empty() {
+            return IPCResponse::Error(IPCError::new(
+                IPCErrorKind::InvalidArgument,
+                "The profile name must not be empty",
+            ));
+        }
+
+        match self.profile_manager.save_profile(name) {
+            Ok(0) => IPCResponse::Error(
+                IPCError::new(
+                    IPCErrorKind::InvalidArgument,
+                    "No output has a wallpaper or settings to save",
+                )
+                .with_subject(name),
+            ),
+            Ok(count) => {
+                tracing::info!("Saved profile [{}] with {} outputs", name, count);
+                IPCResponse::Success
+            }
+            Err(err) => IPCResponse::Error(profiles_write_error(err)),
+        }
+    }
+
+    fn load_profile(&mut self, name: &str) -> IPCResponse {
+        let changes = match self.profile_manager.load_profile(name) {
+            Ok(Some(changes)) => changes,
+            Ok(None) => return IPCResponse::Error(profile_not_found(name)),
+            Err(err) => return IPCResponse::Error(profiles_write_error(err)),
+        };
+        tracing::info!("Loading profile [{}]", name);
+
+        let outputs: Vec<String> = self
+            .rendering_context
+            .get_outputs()
+            .drain()
+            .filter_map(|(_, output)| output.name)
+            .collect();
+
+        // The outputs which aren't connected get their settings once they are
+        let results = changes
+            .into_iter()
+            .filter(|change| outputs.contains(&change.screen))
+            .map(|change| {
+                let result = self.apply_output_change(&change);
+                // Like picking a wallpaper by hand, loading a profile stops the playlists
+                if change.wallpaper.is_some() && result.is_ok() {
+                    self.stop_replaced_playlist(&change.screen);
                 }
-                _ => {
-                    tracing::warn!(
-                        "Unsupported wallpaper type for SetWallpaper request: [{}]",
-                        screen
-                    );
-                    if response
-                        .send(IPCResponse::Error(IPCError::UnsupportedWallpaperType))
-                        .is_err()
-                    {
-                        tracing::info!("Unable to send UnsupportedWallpaperType");
-                    }
-                    return false;
+
+                OutputResult {
+                    screen: change.screen,
+                    error: result.and_then(saved).err(),
+                }
+            })
+            .collect();
+
+        IPCResponse::OutputResults(results)
+    }
+
+    fn start_playlist(&mut self, name: &str, pattern: &str) -> IPCResponse {
+        if !self.playlist_manager.contains(name) {
+            return IPCResponse::Error(playlist_not_found(name));
+        }
+
+        let screens = match self.matching_outputs(pattern) {
+            Ok(screens) => screens,
+            Err(error) => return IPCResponse::Error(error),
+        };
+
+        let results = screens
+            .into_iter()
+            .map(|screen| {
+                let result = match self.playlist_manager.start(name, &screen) {
+                    Ok(id) => {
+                        let id = id.expect("Playlist existence was checked above");
+                        tracing::info!("Started playlist [{}] on output [{}]", name, screen);
+
+                        self.apply_wallpaper(&WallpaperRef::Workshop(id), &screen)
+                            .and_then(saved)
+                    }
+                    Err(err) => Err(playlists_write_error(err)),
+                };
+
+                OutputResult {
+                    screen,
+                    error: result.err(),
                 }
+            })
+            .collect();
+
+        IPCResponse::OutputResults(results)
+    }
+
+    fn step_playlist(&mut self, screen: &str, forward: bool) -> IPCResponse {
+        let id = match self.playlist_manager.step(screen, forward) {
+            Ok(Some(id)) => id,
+            Ok(None) => return IPCResponse::Error(no_playlist_running(screen)),
+            Err(err) => return IPCResponse::Error(playlists_write_error(err)),
+        };
+
+        match self
+            .apply_wallpaper(&WallpaperRef::Workshop(id), screen)
+            .and_then(saved)
+        {
+            Ok(()) => IPCResponse::Success,
+            Err(error) => IPCResponse::Error(error),
+        }
+    }
+
+    fn stop_replaced_playlist(&mut self, screen: &str) {
+        match self.playlist_manager.stop(screen) {
+            Ok(true) => tracing::info!("Stopped playlist on output [{}]", screen),
+            Ok(false) => {}
+            Err(err) => tracing::warn!(
+                "Stopped playlist on output [{}] but failed to save it: {}",
+                screen,
+                err
+            ),
+        }
+    }
+
+    /// Switches the wallpaper of every output whose playlist interval elapsed
+    fn advance_playlists(&mut self) {
+        for (screen, id) in self.playlist_manager.due(Instant::now()) {
+            // A wallpaper failing to load must not stop the playlist, the next one may work
+            if let Err(err) = self
+                .apply_wallpaper(&WallpaperRef::Workshop(id), &screen)
+                .and_then(saved)
+            {
+                tracing::warn!(
+                    "Playlist failed to set wallpaper [{}] on output [{}]: {}",
+                    id,
+                    screen,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Sets the wallpapers of the schedule rules which became active, like `SetWallpaper` would
+    /// but without stopping the playlists
+    fn run_schedule(&mut self) {
+        if !self.scheduler.should_evaluate() {
+            return;
+        }
+
+        let outputs: Vec<String> = self
+            .rendering_context
+            .get_outputs()
+            .drain()
+            .filter_map(|(_, output)| output.name)
+            .collect();
+
+        for (screen, id) in self.scheduler.evaluate(&outputs) {
+            tracing::info!(
+                "Schedule switching output [{}] to wallpaper [{}]",
+                screen,
+                id
+            );
+
+            if let Err(error) = self
+                .apply_wallpaper(&WallpaperRef::Workshop(id), &screen)
+                .and_then(saved)
+            {
+                tracing::warn!(
+                    "Schedule failed to set wallpaper [{}] on output [{}]: {}",
+                    id,
+                    screen,
+                    error
+                );
+            }
+        }
+    }
+
+    fn set_wallpaper(&mut self, wallpaper: &WallpaperRef, screen: &str) -> Result<(), IPCError> {
+        let result = self.load_wallpaper(wallpaper, screen);
+
+        match result {
+            Ok(()) => {
+                tracing::info!("Set wallpaper [{}] for output [{}]", wallpaper, screen);
+                self.events.emit(IPCEvent::WallpaperChanged {
+                    screen: screen.to_owned(),
+                    wallpaper: wallpaper.clone(),
+                });
+            }
+            Err(ref error) => {
+                self.events.emit(IPCEvent::WallpaperLoadFailed {
+                    screen: screen.to_owned(),
+                    wallpaper: wallpaper.clone(),
+                    error: error.clone(),
+                });
             }
+        }
+
+        result
+    }
+
+    fn set_paused(&mut self, screen: &str, paused: Option<bool>) -> IPCResponse {
+        match self.rendering_context.set_paused(screen, paused) {
+            Some(paused) => {
+                tracing::info!(
+                    "Playback {} on output [{}]",
+                    if paused { "paused" } else { "resumed" },
+                    screen
+                );
+                IPCResponse::Success
+            }
+            None => IPCResponse::Error(no_wallpaper_displayed(screen)),
+        }
+    }
+
+    fn set_fps_cap(&mut self, screen: Option<&str>, fps: Option<u32>) -> IPCResponse {
+        if fps == Some(0) {
+            return IPCResponse::Error(IPCError::new(
+                IPCErrorKind::InvalidArgument,
+                "The FPS cap must be greater than zero",
+            ));
+        }
+
+        if !self.rendering_context.set_fps_cap(screen, fps) {
+            return IPCResponse::Error(no_wallpaper_displayed(screen.unwrap_or_default()));
+        }
+
+        let target = screen.unwrap_or("every output");
+        match fps {
+            Some(fps) => tracing::info!("Capped [{}] at {} FPS", target, fps),
+            None => tracing::info!("Removed FPS cap of [{}]", target),
+        }
+
+        if let Some(screen) = screen
+            && let Err(err) = self.profile_manager.save_fps_cap(screen, fps)
+        {
+            return IPCResponse::Error(profiles_write_error(err));
+        }
+
+        IPCResponse::Success
+    }
+
+    fn screenshot(&mut self, screen: &str, path: &Path) -> Result<(), IPCError> {
+        if path.is_relative() {
+            return Err(IPCError::new(
+                IPCErrorKind::InvalidArgument,
+                "The screenshot path must be absolute",
+            )
+            .with_subject(path.to_string_lossy()));
+        }
+
+        if self.rendering_context.wallpaper_project(screen).is_none() {
+            return Err(no_wallpaper_displayed(screen));
+        }
+
+        let Some(image) = self.rendering_context.screenshot(screen) else {
+            return Err(IPCError::new(
+                IPCErrorKind::WallpaperLoadingError,
+                "The wallpaper has no frame to show yet, try again in a moment",
+            )
+            .with_subject(screen));
+        };
+
+        image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(|err| {
+                tracing::warn!("Failed to write screenshot: {}", err);
+                IPCError::new(
+                    IPCErrorKind::InternalError,
+                    "Unable to write the screenshot",
+                )
+                .with_subject(path.to_string_lossy())
+                .with_causes(vec![err.to_string()])
+            })?;
+
+        tracing::info!(
+            "Saved screenshot of output [{}] to {}",
+            screen,
+            path.to_string_lossy()
+        );
+
+        Ok(())
+    }
+
+    fn set_property(&mut self, screen: &str, key: &str, value: &str) -> Result<(), IPCError> {
+        let Some(project) = self.rendering_context.wallpaper_project(screen) else {
+            return Err(no_wallpaper_displayed(screen));
+        };
+
+        let Some(property) = project
+            .general
+            .as_ref()
+            .and_then(|general| general.properties.get(key))
+        else {
+            return Err(IPCError::new(
+                IPCErrorKind::PropertyNotFound,
+                "The wallpaper has no such property",
+            )
+            .with_subject(key));
+        };
+
+        let new_value = property.value.with_value(value).map_err(|err| {
+            IPCError::new(IPCErrorKind::InvalidArgument, "Invalid property value")
+                .with_subject(key)
+                .with_causes(vec![err.to_string()])
+        })?;
 
-            tracing::info!("Set wallpaper for output [{}] with id [{}]", screen, id);
+        self.rendering_context.set_property(screen, key, new_value);
+        tracing::info!(
+            "Set property [{}] to [{}] on output [{}]",
+            key,
+            value,
+            screen
+        );
+
+        Ok(())
+    }
+
+    /// Looks the wallpaper up in the library, rescanning it first if the wallpaper was installed
+    /// since the last scan
+    fn workshop_wallpaper_path(&mut self, id: u64) -> PathBuf {
+        if self.library.get(id).is_none()
+            && let Err(err) = self.library.rescan()
+        {
+            tracing::warn!("Unable to scan the wallpaper library: {}", err);
+        }
+
+        match self.library.get(id) {
+            Some(wallpaper) => wallpaper.path.clone(),
+            // Still try to load it from the main workshop directory, to report why it can't be
+            None => self.library.roots()[0].join(id.to_string()),
+        }
+    }
+
+    /// Reads the configuration file and `wallpapers.conf` again, like when they are edited
+    fn reload(&mut self) -> IPCResponse {
+        let causes: Vec<String> = [self.reload_config(), self.reload_saved_outputs()]
+            .into_iter()
+            .filter_map(Result::err)
+            .map(|err| format!("{:#}", err))
+            .collect();
+
+        if causes.is_empty() {
+            return IPCResponse::Success;
+        }
+
+        IPCResponse::Error(
+            IPCError::new(
+                IPCErrorKind::InvalidConfig,
+                "Unable to reload the configuration, the previous one is still in use",
+            )
+            .with_causes(causes),
+        )
+    }
+
+    /// Reloads the watched files which were written since the last call
+    fn reload_changed_files(&mut self) {
+        let Some(file_watcher) = self.file_watcher.as_mut() else {
+            return;
+        };
+
+        for path in file_watcher.poll() {
+            tracing::info!("{} changed, reloading it", path.to_string_lossy());
+
+            let result = if path == profile_manager::save_path() {
+                self.reload_saved_outputs()
+            } else {
+                self.reload_config()
+            };
 
-            if response.send(IPCResponse::Success).is_err() {
-                tracing::info!("Unable to send Success");
+            // Keep running with the previous settings until the file is fixed
+            if let Err(err) = result {
+                tracing::error!("Reload failed, keeping the previous settings: {:#}", err);
             }
+        }
+    }
 
-            true
-        } else {
+    fn reload_config(&mut self) -> anyhow::Result<()> {
+        let config = Config::load(self.config_file.as_deref())?;
+
+        if config.log_level != self.config.log_level
+            || config.socket != self.config.socket
+            || config.workshop_dirs != self.config.workshop_dirs
+        {
             tracing::warn!(
-                "Received wrong output in SetWallpaper request: [{}]",
-                screen
+                "Changes to log_level, socket and workshop_dirs only apply once the daemon is restarted"
             );
-            if response
-                .send(IPCResponse::Error(IPCError::ScreenNotFound))
-                .is_err()
-            {
+        }
+
+        self.rendering_context
+            .set_transition(Transition::from_config(&config.transition));
+        self.rendering_context.set_video_config(config.video);
+        self.rendering_context
+            .set_fps_cap(None, config.fps_cap.map(NonZeroU32::get));
+        if config.power != self.config.power {
+            self.power_monitor.set_policy(config.power.clone());
+        }
+        self.config = config;
+
+        let outputs: Vec<String> = self
+            .rendering_context
+            .get_outputs()
+            .drain()
+            .filter_map(|(_, output)| output.name)
+            .collect();
+        for screen in outputs {
+            self.apply_output_settings(&screen);
+        }
+
+        tracing::info!("Reloaded the configuration");
+        Ok(())
+    }
+
+    /// Applies the outputs settings edited by hand in `wallpapers.conf`
+    fn reload_saved_outputs(&mut self) -> anyhow::Result<()> {
+        let changes = self.profile_manager.reload().with_context(|| {
+            format!(
+                "unable to read {}",
+                profile_manager::save_path().to_string_lossy()
+            )
+        })?;
+
+        let outputs: Vec<String> = self
+            .rendering_context
+            .get_outputs()
+            .drain()
+            .filter_map(|(_, output)| output.name)
+            .collect();
+
+        // The outputs which aren't connected get their settings once they are
+        for change in changes
+            .into_iter()
+            .filter(|change| outputs.contains(&change.screen))
+        {
+            if let Err(err) = self.apply_output_change(&change).and_then(saved) {
                 tracing::warn!(
-                    "Failed to send ScreenNotFound response for output [{}]: {:?}. \
-                     The IPC channel may be closed; client will not receive error response.",
+                    "Unable to apply the settings of output [{}]: {}",
+                    change.screen,
+                    err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies settings which were saved for the output by other means than the IPC requests,
+    /// like [`AppState::apply_wallpaper`]
+    fn apply_output_change(&mut self, change: &OutputChange) -> Result<Option<IPCError>, IPCError> {
+        match &change.wallpaper {
+            Some(wallpaper) => self.apply_wallpaper(wallpaper, &change.screen),
+            None => {
+                self.apply_output_settings(&change.screen);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Applies the scaling mode, FPS cap and property overrides saved for the given output, the
+    /// configured ones otherwise
+    fn restore_output_settings(&mut self, screen: &str) {
+        self.apply_output_settings(screen);
+
+        for (key, value) in self.profile_manager.load_properties(screen) {
+            if let Err(err) = self.set_property(screen, &key, &value) {
+                tracing::warn!(
+                    "Unable to restore property [{}] on output [{}]: {}",
+                    key,
                     screen,
-                    IPCError::ScreenNotFound
+                    err
                 );
             }
+        }
+    }
+
+    /// Applies the scaling mode and FPS cap saved for the given output, the configured ones
+    /// otherwise
+    fn apply_output_settings(&mut self, screen: &str) {
+        let mode = self
+            .profile_manager
+            .load_scaling(screen)
+            .unwrap_or_else(|| self.config.output_scaling(screen));
+        self.rendering_context.set_scaling(screen, mode);
+
+        let fps_cap = self
+            .profile_manager
+            .load_fps_cap(screen)
+            .or_else(|| self.config.output_fps_cap(screen));
+        self.rendering_context.set_fps_cap(Some(screen), fps_cap);
+    }
+
+    fn load_wallpaper(&mut self, wallpaper: &WallpaperRef, screen: &str) -> Result<(), IPCError> {
+        let outputs = self.rendering_context.get_outputs();
+
+        let Some(output) = outputs.iter().find(|output| match output.1.name.as_ref() {
+            Some(name) => name == screen,
+            None => false,
+        }) else {
+            tracing::warn!(
+                "Received wrong output in SetWallpaper request: [{}]",
+                screen
+            );
+            return Err(IPCError::new(
+                IPCErrorKind::ScreenNotFound,
+                "The specified screen was not found",
+            )
+            .with_subject(screen));
+        };
+
+        let path = match wallpaper {
+            WallpaperRef::Workshop(id) => self.workshop_wallpaper_path(*id),
+            WallpaperRef::Path(path) => {
+                // The daemon working directory has nothing to do with the client's one
+                if path.is_relative() {
+                    return Err(IPCError::new(
+                        IPCErrorKind::InvalidArgument,
+                        "The wallpaper path must be absolute",
+                    )
+                    .with_subject(path.to_string_lossy()));
+                }
+
+                path.clone()
+            }
+        };
+
+        if !path.exists() {
+            tracing::warn!("Wallpaper path does not exist: {:?}", path);
+            return Err(wallpaper_not_found(
+                &path,
+                "The wallpaper directory does not exist",
+            ));
+        }
+
+        if !path.is_dir() {
+            // The wallpaper path is expected to be a directory containing wallpaper resources.
+            tracing::warn!("Wallpaper path is not a directory: {:?}", path);
+            return Err(wallpaper_not_found(
+                &path,
+                "The wallpaper path is not a directory",
+            ));
+        }
+
+        if !path.join("project.json").is_file() {
+            tracing::warn!("Wallpaper directory has no project.json: {:?}", path);
+            return Err(wallpaper_not_found(
+                &path,
+                "The wallpaper directory does not contain a project.json",
+            ));
+        }
+
+        let wallpaper = match Wallpaper::new(path.clone()) {
+            Ok(wallpaper) => wallpaper,
+            Err(e) => {
+                tracing::warn!("Failed to load wallpaper: {:?}", e);
+                return Err(wallpaper_loading_error(&path, e));
+            }
+        };
+
+        match wallpaper {
+            Wallpaper::Video { ref project, .. } => {
+                let Some(file) = project.file.as_ref() else {
+                    tracing::warn!("Wallpaper project file is None for video wallpaper");
+                    return Err(wallpaper_not_found(
+                        &path,
+                        "The video wallpaper does not reference any file",
+                    ));
+                };
+
+                let video_path = path.join(file);
+                if !video_path.exists() {
+                    tracing::warn!("Video file does not exist: {:?}", video_path);
+                    return Err(wallpaper_not_found(
+                        &video_path,
+                        "The video file does not exist",
+                    ));
+                }
 
-            false
+                tracing::info!("Found video file ! (Path : {video_path:?})");
+            }
+            Wallpaper::Scene { .. } => {
+                let scene_pkg_file = path.join("scene.pkg");
+                tracing::info!("Found scene package file ! (Path : {scene_pkg_file:?})");
+            }
+            _ => {
+                tracing::warn!(
+                    "Unsupported wallpaper type for SetWallpaper request: [{}]",
+                    screen
+                );
+                return Err(IPCError::new(
+                    IPCErrorKind::UnsupportedWallpaperType,
+                    format!("{:?} wallpapers are not supported", wallpaper.wp_type()),
+                )
+                .with_subject(path.to_string_lossy()));
+            }
         }
+
+        self.rendering_context
+            .set_wallpaper(output, wallpaper)
+            .map_err(|e| {
+                tracing::warn!("Failed to set up wallpaper renderer: {:?}", e);
+                wallpaper_loading_error(&path, e)
+            })
+    }
+}
+
+fn no_wallpaper_displayed(screen: &str) -> IPCError {
+    IPCError::new(
+        IPCErrorKind::ScreenNotFound,
+        "No wallpaper is displayed on the specified screen",
+    )
+    .with_subject(screen)
+}
+
+fn playlist_not_found(name: &str) -> IPCError {
+    IPCError::new(IPCErrorKind::PlaylistNotFound, "No playlist has this name").with_subject(name)
+}
+
+fn profile_not_found(name: &str) -> IPCError {
+    IPCError::new(IPCErrorKind::ProfileNotFound, "No profile has this name").with_subject(name)
+}
+
+/// Turns the save error of a displayed wallpaper into the result of the request
+fn saved(save_error: Option<IPCError>) -> Result<(), IPCError> {
+    match save_error {
+        Some(error) => Err(error),
+        None => Ok(()),
     }
 }
+
+fn profiles_write_error(error: io::Error) -> IPCError {
+    tracing::warn!("Failed to write profiles: {}", error);
+    IPCError::new(IPCErrorKind::InternalError, "Unable to save the profiles")
+        .with_causes(vec![error.to_string()])
+}
+
+fn playlists_write_error(error: io::Error) -> IPCError {
+    tracing::warn!("Failed to write playlists: {}", error);
+    IPCError::new(IPCErrorKind::InternalError, "Unable to save the playlists")
+        .with_causes(vec![error.to_string()])
+}
+
+fn no_playlist_running(screen: &str) -> IPCError {
+    IPCError::new(
+        IPCErrorKind::PlaylistNotFound,
+        "No playlist is running on the specified screen",
+    )
+    .with_subject(screen)
+}
+
+fn wallpaper_not_found(path: &Path, message: &str) -> IPCError {
+    IPCError::new(IPCErrorKind::WallpaperNotFound, message).with_subject(path.to_string_lossy())
+}
+
+fn wallpaper_loading_error(path: &Path, error: anyhow::Error) -> IPCError {
+    IPCError::new(
+        IPCErrorKind::WallpaperLoadingError,
+        "Unable to load the wallpaper",
+    )
+    .with_subject(path.to_string_lossy())
+    .with_causes(error.chain().map(ToString::to_string).collect())
+}
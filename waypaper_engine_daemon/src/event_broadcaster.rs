@@ -0,0 +1,78 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use waypaper_engine_shared::ipc::IPCEvent;
+
+#[derive(Clone)]
+pub struct EventBroadcaster {
+    subscribers: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+impl EventBroadcaster {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn listen(&self, socket_path: &Path) -> std::io::Result<()> {
+        if socket_path.exists() {
+            fs::remove_file(socket_path)?;
+        }
+
+        let listener = UnixListener::bind(socket_path)?;
+        let subscribers = Arc::clone(&self.subscribers);
+
+        thread::spawn(move || {
+            tracing::info!("Started events channel");
+
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        // Events are sent from the main loop, which must never wait for a
+                        // subscriber not reading them
+                        if let Err(err) = stream.set_nonblocking(true) {
+                            tracing::warn!("Unable to configure event subscriber: {}", err);
+                            continue;
+                        }
+
+                        tracing::debug!("New event subscriber");
+                        subscribers.lock().unwrap().push(stream);
+                    }
+                    Err(err) => tracing::warn!("Failed to accept event subscriber: {}", err),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn emit(&self, event: IPCEvent) {
+        tracing::debug!("Broadcasting event : [{:?}]", event);
+
+        let mut line = match serde_json::to_string(&event) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::warn!("Failed to serialize event {:?}: {}", event, err);
+                return;
+            }
+        };
+        line.push('\n');
+
+        // A subscriber whose socket buffer is full stopped reading its events, it is dropped
+        // rather than waited for
+        self.subscribers.lock().unwrap().retain_mut(|stream| {
+            match stream.write_all(line.as_bytes()) {
+                Ok(_) => true,
+                Err(err) => {
+                    tracing::debug!("Dropping event subscriber: {}", err);
+                    false
+                }
+            }
+        });
+    }
+}
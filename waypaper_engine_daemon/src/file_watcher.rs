@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// Editors either rewrite files in place or replace them with a renamed temporary file
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+
+/// Watches files for changes with inotify, without blocking.
+///
+/// The parent directories are watched rather than the files themselves, so that files which
+/// are replaced, or created later on, are still watched.
+pub struct FileWatcher {
+    fd: OwnedFd,
+    /// Watched directories, by watch descriptor
+    directories: HashMap<i32, PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new(files: &[PathBuf]) -> io::Result<FileWatcher> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut directories = HashMap::new();
+        for directory in files.iter().filter_map(|file| file.parent()) {
+            // Relative paths in the current directory have an empty parent
+            let watched = if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                directory
+            };
+            let path = CString::new(watched.as_os_str().as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+            let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            directories.insert(wd, directory.to_path_buf());
+        }
+
+        Ok(FileWatcher {
+            fd,
+            directories,
+            files: files.to_vec(),
+        })
+    }
+
+    /// Returns the watched files written since the last call, each once
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            // Nothing left to read, or an error we can't do anything about
+            let Ok(read) = usize::try_from(read) else {
+                break;
+            };
+            if read == 0 {
+                break;
+            }
+
+            let mut offset = 0;
+            while offset + size_of::<libc::inotify_event>() <= read {
+                let event = unsafe {
+                    buffer
+                        .as_ptr()
+                        .add(offset)
+                        .cast::<libc::inotify_event>()
+                        .read_unaligned()
+                };
+                let name_start = offset + size_of::<libc::inotify_event>();
+                let name_end = (name_start + event.len as usize).min(read);
+                offset = name_end;
+
+                // The name is padded with NUL bytes
+                let name = &buffer[name_start..name_end];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+
+                let Some(directory) = self.directories.get(&event.wd) else {
+                    continue;
+                };
+
+                let path = directory.join(OsStr::from_bytes(name));
+                if self.files.contains(&path) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        changed
+    }
+}
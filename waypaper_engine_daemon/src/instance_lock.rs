@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fs::{self, File, TryLockError};
+use std::path::Path;
+
+use waypaper_engine_shared::{get_events_socket_path, get_lock_file_path};
+
+/// Held for the whole daemon lifetime, the lock is released by the OS when the process exits
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    pub fn acquire(socket_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let lock_path = get_lock_file_path(socket_path);
+        let file = File::create(&lock_path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(format!(
+                    "Another daemon is already running (lock file: {})",
+                    lock_path.to_string_lossy()
+                )
+                .into());
+            }
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+
+        // We own the lock, so any socket still lying around was left by a crashed daemon
+        for path in [
+            socket_path.to_path_buf(),
+            get_events_socket_path(socket_path),
+        ] {
+            if path.try_exists()? {
+                tracing::warn!("Removing stale socket {}", path.to_string_lossy());
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(Self { _file: file })
+    }
+}
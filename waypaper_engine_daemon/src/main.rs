@@ -1,32 +1,82 @@
+use clap::Parser;
 use std::error::Error;
+use std::path::PathBuf;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
+use waypaper_engine_shared::config::{Config, LogLevel};
 
 use crate::app_state::AppState;
 
 mod app_state;
 mod egl;
-mod file_reading_utils;
+mod event_broadcaster;
+mod file_watcher;
+mod instance_lock;
+mod playlist_manager;
+mod power;
 mod rendering_backends;
-mod scene_package;
-mod tex_file;
+mod scheduler;
+mod solar;
+mod transition;
 mod wallpaper;
 mod wallpaper_renderer;
 mod wl_renderer;
 mod profile_manager;
 
+#[derive(Parser)]
+struct Args {
+    /// Path of the configuration file (defaults to $XDG_CONFIG_HOME/waypaper_engine/config.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Path of the IPC socket (defaults to $XDG_RUNTIME_DIR/waypaper-engine-$WAYLAND_DISPLAY.sock)
+    #[arg(long)]
+    socket: Option<PathBuf>,
+    /// Wallpaper Engine workshop directory, can be repeated (defaults to the configured ones,
+    /// $WAYPAPER_ENGINE_WORKSHOP_DIR, or the workshop directories of the Steam libraries)
+    #[arg(long)]
+    workshop_dir: Vec<PathBuf>,
+}
+
 #[global_allocator]
 static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let config = Config::load(args.config.as_deref());
+    let log_level = config
+        .as_ref()
+        .map_or(LogLevel::default(), |config| config.log_level);
+
     tracing_subscriber::fmt()
         .with_env_filter(
             EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
+                .with_default_directive(level_filter(log_level).into())
                 .from_env_lossy(),
         )
         .init();
 
-    let mut app = AppState::new(waypaper_engine_shared::get_wpe_dir());
+    let config = config.inspect_err(|err| tracing::error!("Invalid configuration: {}", err))?;
+
+    let socket_path = args.socket.unwrap_or_else(|| config.socket_path());
+
+    let workshop_dirs = if args.workshop_dir.is_empty() {
+        &config.workshop_dirs
+    } else {
+        &args.workshop_dir
+    };
+    let wpe_dirs = waypaper_engine_shared::get_wpe_dirs(workshop_dirs)?;
+
+    let mut app = AppState::new(wpe_dirs, socket_path, args.config, config);
     app.run()
 }
+
+fn level_filter(log_level: LogLevel) -> LevelFilter {
+    match log_level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
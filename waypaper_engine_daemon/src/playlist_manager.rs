@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use waypaper_engine_shared::ipc::{Playlist, PlaylistOrder};
+
+use crate::profile_manager::{config_dir, read_or_set_aside, write_atomically};
+
+pub struct PlaylistManager {
+    playlists: HashMap<String, Playlist>,
+    /// Playlists running on each output
+    running: HashMap<String, RunningPlaylist>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RunningPlaylist {
+    name: String,
+    /// Indices of the playlist wallpapers, in playing order
+    order: Vec<usize>,
+    position: usize,
+    // The timer restarts from scratch when the daemon does
+    #[serde(skip, default = "Instant::now")]
+    next_change: Instant,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedPlaylists {
+    playlists: HashMap<String, Playlist>,
+    running: HashMap<String, RunningPlaylist>,
+}
+
+impl PlaylistManager {
+    pub fn new() -> PlaylistManager {
+        let saved = read_or_set_aside(read_save(), &save_path());
+        let mut manager = PlaylistManager {
+            playlists: saved.playlists,
+            running: saved.running,
+        };
+
+        // The file may have been edited by hand
+        manager
+            .playlists
+            .retain(|name, playlist| match validate_playlist(playlist) {
+                Ok(()) => true,
+                Err(reason) => {
+                    tracing::warn!("Ignoring saved playlist [{}]: {}", name, reason);
+                    false
+                }
+            });
+
+        // Resume the playlists which were running when the daemon stopped
+        let now = Instant::now();
+        let playlists = &manager.playlists;
+        manager.running.retain(|screen, running| {
+            let Some(playlist) = playlists.get(&running.name) else {
+                return false;
+            };
+
+            if !is_play_order(&running.order, playlist) {
+                running.order = play_order(playlist);
+                running.position = 0;
+            }
+            running.position = running.position.min(running.order.len() - 1);
+            running.next_change = now + interval(playlist);
+
+            tracing::info!(
+                "Resuming playlist [{}] on output [{}]",
+                running.name,
+                screen
+            );
+            true
+        });
+
+        manager
+    }
+
+    pub fn playlists(&self) -> Vec<Playlist> {
+        let mut playlists: Vec<_> = self.playlists.values().cloned().collect();
+        playlists.sort_by(|a, b| a.name.cmp(&b.name));
+        playlists
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.playlists.contains_key(name)
+    }
+
+    pub fn running_playlist(&self, screen: &str) -> Option<&str> {
+        self.running
+            .get(screen)
+            .map(|running| running.name.as_str())
+    }
+
+    /// The playlist must have been validated with `validate_playlist`. Nothing changes if it
+    /// can't be saved.
+    pub fn save_playlist(&mut self, playlist: Playlist) -> io::Result<()> {
+        self.update(|manager| {
+            // Outputs already playing it start over with the new content
+            for running in manager.running.values_mut() {
+                if running.name == playlist.name {
+                    running.order = play_order(&playlist);
+                    running.position = 0;
+                    running.next_change = Instant::now() + interval(&playlist);
+                }
+            }
+
+            manager.playlists.insert(playlist.name.clone(), playlist);
+        })
+    }
+
+    /// Returns `false` if no playlist has this name. Nothing changes if it can't be saved.
+    pub fn delete_playlist(&mut self, name: &str) -> io::Result<bool> {
+        if !self.playlists.contains_key(name) {
+            return Ok(false);
+        }
+
+        self.update(|manager| {
+            manager.playlists.remove(name);
+            manager.running.retain(|_, running| running.name != name);
+        })?;
+
+        Ok(true)
+    }
+
+    /// Starts the playlist on the given output, returns the first wallpaper to display or `None`
+    /// if no playlist has this name. Nothing changes if it can't be saved.
+    pub fn start(&mut self, name: &str, screen: &str) -> io::Result<Option<u64>> {
+        let Some(playlist) = self.playlists.get(name) else {
+            return Ok(None);
+        };
+        let running = RunningPlaylist {
+            name: name.to_owned(),
+            order: play_order(playlist),
+            position: 0,
+            next_change: Instant::now() + interval(playlist),
+        };
+        let id = playlist.wallpapers[running.order[0]];
+
+        self.update(|manager| {
+            manager.running.insert(screen.to_owned(), running);
+        })?;
+
+        Ok(Some(id))
+    }
+
+    /// Returns `false` if no playlist was running on the given output.
+    ///
+    /// The playlist is stopped even if that can't be saved, so that it doesn't replace the
+    /// wallpaper picked in its place. It will only resume when the daemon restarts.
+    pub fn stop(&mut self, screen: &str) -> io::Result<bool> {
+        if self.running.remove(screen).is_none() {
+            return Ok(false);
+        }
+
+        self.write()?;
+        Ok(true)
+    }
+
+    /// Moves the playlist running on the given output, returns the wallpaper to display or `None`
+    /// if no playlist is running there. Nothing changes if it can't be saved.
+    pub fn step(&mut self, screen: &str, forward: bool) -> io::Result<Option<u64>> {
+        let Some(running) = self.running.get(screen) else {
+            return Ok(None);
+        };
+        let Some(playlist) = self.playlists.get(&running.name) else {
+            return Ok(None);
+        };
+
+        let mut running = running.clone();
+        let id = step(&mut running, playlist, forward);
+
+        self.update(|manager| {
+            manager.running.insert(screen.to_owned(), running);
+        })?;
+
+        Ok(Some(id))
+    }
+
+    /// Advances every playlist whose interval elapsed, returns the wallpapers to display.
+    ///
+    /// The playlists keep going when they can't be saved, they only lose their position if the
+    /// daemon restarts.
+    pub fn due(&mut self, now: Instant) -> Vec<(String, u64)> {
+        let mut changes = vec![];
+
+        for (screen, running) in &mut self.running {
+            if running.next_change > now {
+                continue;
+            }
+
+            if let Some(playlist) = self.playlists.get(&running.name) {
+                changes.push((screen.clone(), step(running, playlist, true)));
+            }
+        }
+
+        if !changes.is_empty()
+            && let Err(err) = self.write()
+        {
+            tracing::warn!("Failed to save the playlists positions: {}", err);
+        }
+
+        changes
+    }
+
+    /// Applies `change` then saves the playlists, the change is undone if they can't be saved
+    fn update(&mut self, change: impl FnOnce(&mut Self)) -> io::Result<()> {
+        let playlists = self.playlists.clone();
+        let running = self.running.clone();
+
+        change(self);
+
+        self.write().inspect_err(|_| {
+            self.playlists = playlists;
+            self.running = running;
+        })
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let saved = SavedPlaylists {
+            playlists: self.playlists.clone(),
+            running: self.running.clone(),
+        };
+
+        write_atomically(&save_path(), &saved)
+    }
+}
+
+/// Checks the playlists given by the user, or read from the saved ones
+pub fn validate_playlist(playlist: &Playlist) -> Result<(), &'static str> {
+    if playlist.name.is_empty() {
+        return Err("The playlist name must not be empty");
+    }
+
+    if playlist.wallpapers.is_empty() {
+        return Err("The playlist must contain at least one wallpaper");
+    }
+
+    if playlist.interval_secs == 0 {
+        return Err("The playlist interval must be greater than zero");
+    }
+
+    // Keeps the playlist timers far from overflowing `Instant`
+    if playlist.interval_secs > u64::from(u32::MAX) {
+        return Err("The playlist interval is too long");
+    }
+
+    Ok(())
+}
+
+fn step(running: &mut RunningPlaylist, playlist: &Playlist, forward: bool) -> u64 {
+    let len = running.order.len();
+
+    if forward {
+        running.position += 1;
+
+        if running.position >= len {
+            running.position = 0;
+
+            if playlist.order == PlaylistOrder::Shuffle {
+                running.order = play_order(playlist);
+            }
+        }
+    } else {
+        running.position = running.position.checked_sub(1).unwrap_or(len - 1);
+    }
+
+    running.next_change = Instant::now() + interval(playlist);
+
+    playlist.wallpapers[running.order[running.position]]
+}
+
+/// Whether `order` plays each wallpaper of the playlist once
+fn is_play_order(order: &[usize], playlist: &Playlist) -> bool {
+    let mut indices = order.to_vec();
+    indices.sort_unstable();
+
+    indices.into_iter().eq(0..playlist.wallpapers.len())
+}
+
+fn play_order(playlist: &Playlist) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..playlist.wallpapers.len()).collect();
+
+    if playlist.order == PlaylistOrder::Shuffle {
+        order.shuffle(&mut rand::thread_rng());
+    }
+
+    order
+}
+
+fn interval(playlist: &Playlist) -> Duration {
+    Duration::from_secs(playlist.interval_secs)
+}
+
+fn read_save() -> io::Result<SavedPlaylists> {
+    let file = File::open(save_path())?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+fn save_path() -> PathBuf {
+    config_dir().join("playlists.conf")
+}
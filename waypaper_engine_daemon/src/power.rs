@@ -0,0 +1,335 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use waypaper_engine_shared::config::{BatteryAction, PowerConfig};
+
+// Battery levels change slowly, no need to read sysfs more often than this
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Restrictions applied to every output to save power
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerSaving {
+    Pause,
+    FpsCap(u32),
+    Static,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PowerState {
+    on_battery: bool,
+    /// Average level of the system batteries, in percent
+    capacity: Option<u8>,
+}
+
+/// Periodically reads the power supplies state to decide when to save power
+pub struct PowerMonitor {
+    policy: PowerConfig,
+    last_check: Option<Instant>,
+    power_saving: Option<PowerSaving>,
+}
+
+impl PowerMonitor {
+    pub fn new(policy: PowerConfig) -> PowerMonitor {
+        PowerMonitor {
+            policy,
+            last_check: None,
+            power_saving: None,
+        }
+    }
+
+    /// Replaces the policy, the power supplies are checked again on the next `poll`
+    pub fn set_policy(&mut self, policy: PowerConfig) {
+        self.policy = policy;
+        self.last_check = None;
+    }
+
+    /// Returns the restrictions to apply when they changed since the last call
+    pub fn poll(&mut self) -> Option<Option<PowerSaving>> {
+        // Still polled once after disabling the policy, to lift the current restrictions
+        if (self.policy.on_battery == BatteryAction::None && self.power_saving.is_none())
+            || self
+                .last_check
+                .is_some_and(|last_check| last_check.elapsed() < POWER_CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(Instant::now());
+
+        let state = read_power_state(&self.policy.power_supply_root);
+        let saving = self.policy.on_battery != BatteryAction::None
+            && state.on_battery
+            && state
+                .capacity
+                .is_none_or(|capacity| capacity <= self.policy.battery_threshold);
+
+        let power_saving = saving.then(|| match self.policy.on_battery {
+            BatteryAction::Pause => PowerSaving::Pause,
+            BatteryAction::LowerFps => PowerSaving::FpsCap(self.policy.battery_fps.get()),
+            BatteryAction::Static => PowerSaving::Static,
+            BatteryAction::None => unreachable!("Power saving is disabled"),
+        });
+
+        if power_saving == self.power_saving {
+            return None;
+        }
+
+        match power_saving {
+            Some(power_saving) => tracing::info!(
+                "Running on battery ({}%), saving power: {:?}",
+                state
+                    .capacity
+                    .map_or("unknown".to_owned(), |capacity| capacity.to_string()),
+                power_saving
+            ),
+            None => tracing::info!("Stopped saving power"),
+        }
+
+        self.power_saving = power_saving;
+        Some(power_saving)
+    }
+}
+
+/// Reads the state of the power supplies found in `root`. Missing or unreadable files are
+/// skipped, so that a machine without any battery is simply never on battery.
+fn read_power_state(root: &Path) -> PowerState {
+    let Ok(entries) = fs::read_dir(root) else {
+        tracing::debug!(
+            "Unable to read power supplies from {}",
+            root.to_string_lossy()
+        );
+        return PowerState::default();
+    };
+
+    let mut external_power = false;
+    let mut discharging = false;
+    let mut capacities = vec![];
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let read = |name: &str| {
+            fs::read_to_string(path.join(name))
+                .ok()
+                .map(|value| value.trim().to_owned())
+        };
+
+        match read("type").as_deref() {
+            // Peripherals such as mice report their own battery, which doesn't power us
+            Some("Battery") if read("scope").as_deref() != Some("Device") => {
+                if read("status").as_deref() == Some("Discharging") {
+                    discharging = true;
+                }
+
+                if let Some(capacity) = read("capacity").and_then(|value| value.parse::<u8>().ok())
+                {
+                    capacities.push(capacity.min(100));
+                }
+            }
+            Some("Mains" | "USB") if read("online").as_deref() == Some("1") => {
+                external_power = true;
+            }
+            _ => {}
+        }
+    }
+
+    let capacity = (!capacities.is_empty()).then(|| {
+        let total: u32 = capacities.iter().map(|&capacity| u32::from(capacity)).sum();
+        (total / capacities.len() as u32) as u8
+    });
+
+    PowerState {
+        on_battery: discharging && !external_power,
+        capacity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    use super::*;
+
+    /// A power_supply directory, removed when dropped
+    struct FakePowerSupplies {
+        root: PathBuf,
+    }
+
+    impl FakePowerSupplies {
+        fn new(name: &str) -> FakePowerSupplies {
+            let root =
+                env::temp_dir().join(format!("waypaper_engine_power_{}_{}", process::id(), name));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+
+            FakePowerSupplies { root }
+        }
+
+        fn supply(&self, name: &str, attributes: &[(&str, &str)]) -> &FakePowerSupplies {
+            let dir = self.root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(dir.join(attribute), format!("{value}\n")).unwrap();
+            }
+
+            self
+        }
+
+        fn battery(&self, name: &str, status: &str, capacity: &str) -> &FakePowerSupplies {
+            self.supply(
+                name,
+                &[
+                    ("type", "Battery"),
+                    ("status", status),
+                    ("capacity", capacity),
+                ],
+            )
+        }
+
+        fn state(&self) -> PowerState {
+            read_power_state(&self.root)
+        }
+
+        fn monitor(&self, on_battery: BatteryAction, battery_threshold: u8) -> PowerMonitor {
+            PowerMonitor::new(self.policy(on_battery, battery_threshold))
+        }
+
+        fn policy(&self, on_battery: BatteryAction, battery_threshold: u8) -> PowerConfig {
+            PowerConfig {
+                on_battery,
+                battery_threshold,
+                power_supply_root: self.root.clone(),
+                ..PowerConfig::default()
+            }
+        }
+    }
+
+    impl Drop for FakePowerSupplies {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn ac_online() {
+        let supplies = FakePowerSupplies::new("ac_online");
+        supplies
+            .supply("AC", &[("type", "Mains"), ("online", "1")])
+            .battery("BAT0", "Charging", "80");
+
+        assert_eq!(
+            supplies.state(),
+            PowerState {
+                on_battery: false,
+                capacity: Some(80)
+            }
+        );
+
+        // Some firmwares report the battery as discharging while plugged in
+        supplies.battery("BAT0", "Discharging", "80");
+        assert!(!supplies.state().on_battery);
+    }
+
+    #[test]
+    fn discharging() {
+        let supplies = FakePowerSupplies::new("discharging");
+        supplies
+            .supply("AC", &[("type", "Mains"), ("online", "0")])
+            .battery("BAT0", "Discharging", "42");
+
+        assert_eq!(
+            supplies.state(),
+            PowerState {
+                on_battery: true,
+                capacity: Some(42)
+            }
+        );
+    }
+
+    #[test]
+    fn device_batteries_are_ignored() {
+        let supplies = FakePowerSupplies::new("device_battery");
+        supplies.supply(
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+                ("capacity", "10"),
+            ],
+        );
+
+        assert_eq!(supplies.state(), PowerState::default());
+    }
+
+    #[test]
+    fn batteries_are_averaged() {
+        let supplies = FakePowerSupplies::new("two_batteries");
+        supplies
+            .battery("BAT0", "Discharging", "30")
+            .battery("BAT1", "Unknown", "61");
+
+        assert_eq!(
+            supplies.state(),
+            PowerState {
+                on_battery: true,
+                capacity: Some(45)
+            }
+        );
+    }
+
+    #[test]
+    fn missing_root() {
+        let supplies = FakePowerSupplies::new("missing_root");
+        fs::remove_dir_all(&supplies.root).unwrap();
+
+        assert_eq!(supplies.state(), PowerState::default());
+    }
+
+    #[test]
+    fn threshold_boundary() {
+        let supplies = FakePowerSupplies::new("threshold");
+        supplies.battery("BAT0", "Discharging", "41");
+
+        let mut monitor = supplies.monitor(BatteryAction::Pause, 40);
+        assert_eq!(monitor.poll(), None);
+
+        supplies.battery("BAT0", "Discharging", "40");
+        monitor.set_policy(supplies.policy(BatteryAction::Pause, 40));
+        assert_eq!(monitor.poll(), Some(Some(PowerSaving::Pause)));
+    }
+
+    #[test]
+    fn polled_at_most_every_interval() {
+        let supplies = FakePowerSupplies::new("interval");
+        supplies.battery("BAT0", "Discharging", "50");
+
+        let mut monitor = supplies.monitor(BatteryAction::Static, 100);
+        assert_eq!(monitor.poll(), Some(Some(PowerSaving::Static)));
+
+        // Plugged in, but not checked again yet
+        supplies.supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(monitor.poll(), None);
+    }
+
+    #[test]
+    fn disabling_the_policy_lifts_the_restrictions() {
+        let supplies = FakePowerSupplies::new("disable_policy");
+        supplies.battery("BAT0", "Discharging", "20");
+
+        let mut monitor = supplies.monitor(BatteryAction::LowerFps, 100);
+        assert_eq!(
+            monitor.poll(),
+            Some(Some(PowerSaving::FpsCap(
+                PowerConfig::default().battery_fps.get()
+            )))
+        );
+
+        monitor.set_policy(supplies.policy(BatteryAction::None, 100));
+        assert_eq!(monitor.poll(), Some(None));
+
+        // Nothing left to lift
+        monitor.set_policy(supplies.policy(BatteryAction::None, 100));
+        assert_eq!(monitor.poll(), None);
+    }
+}
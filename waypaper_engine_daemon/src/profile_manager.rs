@@ -1,47 +1,558 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::path::PathBuf;
-use std::{env, fs};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use serde::{Deserialize, Serialize};
+use waypaper_engine_shared::ipc::{Profile, ProfileOutput, ScalingMode, WallpaperRef};
 
 pub struct ProfileManager {
-    wallpapers: HashMap<String, u64>,
+    /// Settings of each output, by monitor (`make model serial`) or by output name when the
+    /// monitor is unknown
+    outputs: HashMap<String, SavedOutput>,
+    /// Named snapshots of `outputs`, saved in `profiles.conf`
+    profiles: BTreeMap<String, HashMap<String, SavedOutput>>,
+    /// Key of the saved settings of each output seen since the daemon started, by output name
+    monitors: HashMap<String, String>,
+}
+
+/// An output whose saved settings were edited
+pub struct OutputChange {
+    pub screen: String,
+    /// Set when the wallpaper, or its properties, must be loaded again
+    pub wallpaper: Option<WallpaperRef>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedOutput {
+    wallpaper: Option<SavedWallpaper>,
+    /// Property overrides of the wallpaper, as sent by the user
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    properties: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scaling: Option<ScalingMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fps_cap: Option<u32>,
+}
+
+/// `wallpapers.conf` used to map each output to its wallpaper only
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedOutputCompat {
+    Wallpaper(SavedWallpaper),
+    Output(SavedOutput),
+}
+
+impl From<SavedOutputCompat> for SavedOutput {
+    fn from(saved: SavedOutputCompat) -> Self {
+        match saved {
+            SavedOutputCompat::Wallpaper(wallpaper) => SavedOutput {
+                wallpaper: Some(wallpaper),
+                ..Default::default()
+            },
+            SavedOutputCompat::Output(output) => output,
+        }
+    }
+}
+
+/// On-disk form of a `WallpaperRef`: workshop ids are saved as plain numbers, like they always were
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedWallpaper {
+    Workshop(u64),
+    Path(PathBuf),
+}
+
+impl From<WallpaperRef> for SavedWallpaper {
+    fn from(wallpaper: WallpaperRef) -> Self {
+        match wallpaper {
+            WallpaperRef::Workshop(id) => SavedWallpaper::Workshop(id),
+            WallpaperRef::Path(path) => SavedWallpaper::Path(path),
+        }
+    }
+}
+
+impl From<SavedWallpaper> for WallpaperRef {
+    fn from(wallpaper: SavedWallpaper) -> Self {
+        match wallpaper {
+            SavedWallpaper::Workshop(id) => WallpaperRef::Workshop(id),
+            SavedWallpaper::Path(path) => WallpaperRef::Path(path),
+        }
+    }
 }
 
 impl ProfileManager {
     pub fn new() -> ProfileManager {
+        // Load the save right away so that saving an output doesn't drop the others
         ProfileManager {
-            wallpapers: HashMap::new(),
+            outputs: read_or_set_aside(read_save(), &save_path()),
+            profiles: read_or_set_aside(read_profiles(), &profiles_path()),
+            monitors: HashMap::new(),
+        }
+    }
+
+    /// Makes the settings of the given output follow its monitor rather than the connector it is
+    /// plugged into. `monitor` is `None` when the monitor can't be told apart from others, the
+    /// settings are then saved by output name.
+    pub fn register_output(&mut self, screen: &str, monitor: Option<String>) {
+        let key = monitor.unwrap_or_else(|| screen.to_owned());
+        self.monitors.insert(screen.to_owned(), key.clone());
+
+        if key == screen {
+            return;
+        }
+
+        // Settings used to be saved by output name, they now belong to the monitor found there
+        if migrate_output(&mut self.outputs, screen, &key) {
+            tracing::info!(
+                "Moved the saved settings of output [{}] to monitor [{}]",
+                screen,
+                key
+            );
+            if let Err(err) = self.write() {
+                tracing::warn!("Unable to write the saved wallpapers: {}", err);
+            }
+        }
+
+        let mut profiles_migrated = false;
+        for outputs in self.profiles.values_mut() {
+            profiles_migrated |= migrate_output(outputs, screen, &key);
+        }
+        if profiles_migrated && let Err(err) = self.write_profiles() {
+            tracing::warn!("Unable to write profiles: {}", err);
+        }
+    }
+
+    /// Reads the save again after it was edited, returns the outputs whose settings changed.
+    /// The current settings are kept if the save can't be read.
+    pub fn reload(&mut self) -> io::Result<Vec<OutputChange>> {
+        let outputs = match read_save() {
+            Ok(outputs) => outputs,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut changes: Vec<_> = self
+            .monitors
+            .iter()
+            .filter_map(|(screen, key)| {
+                let previous = self.outputs.get(key).cloned().unwrap_or_default();
+                let output = outputs.get(key).cloned().unwrap_or_default();
+                if previous == output {
+                    return None;
+                }
+
+                // Properties removed from the save are only reset by loading the wallpaper again
+                let reload_wallpaper = previous.wallpaper != output.wallpaper
+                    || previous.properties != output.properties;
+
+                Some(OutputChange {
+                    screen: screen.clone(),
+                    wallpaper: output
+                        .wallpaper
+                        .filter(|_| reload_wallpaper)
+                        .map(WallpaperRef::from),
+                })
+            })
+            .collect();
+        changes.sort_by(|a, b| a.screen.cmp(&b.screen));
+
+        self.outputs = outputs;
+        Ok(changes)
+    }
+
+    pub fn save_wallpaper(&mut self, wallpaper: &WallpaperRef, screen: &str) -> io::Result<()> {
+        let wallpaper = SavedWallpaper::from(wallpaper.clone());
+        let output = self.outputs.entry(self.key(screen)).or_default();
+
+        // Property overrides only make sense for the wallpaper they were set on
+        if output.wallpaper.as_ref() != Some(&wallpaper) {
+            output.properties.clear();
+        }
+        output.wallpaper = Some(wallpaper);
+
+        self.write()
+    }
+
+    pub fn save_property(&mut self, screen: &str, key: &str, value: &str) -> io::Result<()> {
+        self.outputs
+            .entry(self.key(screen))
+            .or_default()
+            .properties
+            .insert(key.to_owned(), value.to_owned());
+
+        self.write()
+    }
+
+    pub fn save_scaling(&mut self, screen: &str, mode: ScalingMode) -> io::Result<()> {
+        self.outputs.entry(self.key(screen)).or_default().scaling = Some(mode);
+
+        self.write()
+    }
+
+    pub fn save_fps_cap(&mut self, screen: &str, fps_cap: Option<u32>) -> io::Result<()> {
+        self.outputs.entry(self.key(screen)).or_default().fps_cap = fps_cap;
+
+        self.write()
+    }
+
+    pub fn load_wallpaper(&mut self, screen: &str) -> Option<WallpaperRef> {
+        self.load_output(screen)?
+            .wallpaper
+            .clone()
+            .map(WallpaperRef::from)
+    }
+
+    pub fn load_properties(&mut self, screen: &str) -> HashMap<String, String> {
+        self.load_output(screen)
+            .map(|output| output.properties.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn load_scaling(&mut self, screen: &str) -> Option<ScalingMode> {
+        self.load_output(screen)?.scaling
+    }
+
+    pub fn load_fps_cap(&mut self, screen: &str) -> Option<u32> {
+        self.load_output(screen)?.fps_cap
+    }
+
+    pub fn profiles(&self) -> Vec<Profile> {
+        self.profiles
+            .iter()
+            .map(|(name, outputs)| {
+                let mut outputs: Vec<_> = outputs
+                    .iter()
+                    .map(|(key, output)| ProfileOutput {
+                        screen: key.clone(),
+                        wallpaper: output.wallpaper.clone().map(WallpaperRef::from),
+                        properties: output.properties.clone().into_iter().collect(),
+                        scaling: output.scaling,
+                        fps_cap: output.fps_cap,
+                    })
+                    .collect();
+                outputs.sort_by(|a, b| a.screen.cmp(&b.screen));
+
+                Profile {
+                    name: name.clone(),
+                    outputs,
+                }
+            })
+            .collect()
+    }
+
+    /// Saves the current settings of every output under `name`, returns the number of outputs
+    pub fn save_profile(&mut self, name: &str) -> io::Result<usize> {
+        let outputs = self.outputs.clone();
+        let count = outputs.len();
+
+        self.profiles.insert(name.to_owned(), outputs);
+        self.write_profiles()?;
+
+        Ok(count)
+    }
+
+    /// Makes the settings saved in the profile the current ones, returns the connected outputs
+    /// they apply to, or `None` if there is no such profile. The other outputs keep their
+    /// settings.
+    pub fn load_profile(&mut self, name: &str) -> io::Result<Option<Vec<OutputChange>>> {
+        let Some(profile) = self.profiles.get(name) else {
+            return Ok(None);
+        };
+
+        let mut changes: Vec<_> = self
+            .monitors
+            .iter()
+            .filter_map(|(screen, key)| {
+                Some(OutputChange {
+                    screen: screen.clone(),
+                    wallpaper: profile.get(key)?.wallpaper.clone().map(WallpaperRef::from),
+                })
+            })
+            .collect();
+        changes.sort_by(|a, b| a.screen.cmp(&b.screen));
+
+        self.outputs.extend(profile.clone());
+        self.write()?;
+
+        Ok(Some(changes))
+    }
+
+    /// Returns `false` if there is no such profile
+    pub fn delete_profile(&mut self, name: &str) -> io::Result<bool> {
+        if self.profiles.remove(name).is_none() {
+            return Ok(false);
         }
+
+        self.write_profiles()?;
+        Ok(true)
     }
 
-    pub fn save_wallpaper(&mut self, id: u64, screen: &str) {
-        let path = save_dir();
-        self.wallpapers.insert(screen.to_owned(), id);
-        let file = File::create(&path).expect("Unable to create save file");
-        serde_json::to_writer_pretty(file, &self.wallpapers).expect("Unable to write save into file");
+    /// The key the settings of the output are saved under
+    fn key(&self, screen: &str) -> String {
+        self.monitors
+            .get(screen)
+            .cloned()
+            .unwrap_or_else(|| screen.to_owned())
     }
 
-    pub fn load_wallpaper(&mut self, screen: &str) -> Option<u64> {
-        if !self.wallpapers.contains_key(screen) {
-            let file_path = save_dir();
-            let file = File::open(&file_path).ok()?;
-            self.wallpapers = serde_json::from_reader(file).ok()?;
+    fn load_output(&mut self, screen: &str) -> Option<&SavedOutput> {
+        let key = self.key(screen);
+        if !self.outputs.contains_key(&key) {
+            self.outputs = read_save().ok()?;
         }
-        self.wallpapers.get(screen).copied()
+        self.outputs.get(&key)
+    }
+
+    fn write(&self) -> io::Result<()> {
+        write_atomically(&save_path(), &self.outputs)
+    }
+
+    fn write_profiles(&self) -> io::Result<()> {
+        write_atomically(&profiles_path(), &self.profiles)
+    }
+}
+
+/// Moves the settings saved under the output name to the monitor, unless it already has some.
+/// Returns whether anything moved.
+fn migrate_output(outputs: &mut HashMap<String, SavedOutput>, screen: &str, monitor: &str) -> bool {
+    if outputs.contains_key(monitor) {
+        return false;
+    }
+
+    match outputs.remove(screen) {
+        Some(output) => {
+            outputs.insert(monitor.to_owned(), output);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Writes `value` as JSON to a temporary file, then moves it over `path`, so that a crash can't
+/// leave a truncated file behind
+pub(crate) fn write_atomically(path: &Path, value: &impl Serialize) -> io::Result<()> {
+    let mut extension = path.extension().unwrap_or_default().to_owned();
+    extension.push(format!(".{}", process::id()));
+    let temp_path = path.with_extension(extension);
+
+    if let Err(err) = write_json(&temp_path, value).and_then(|()| fs::rename(&temp_path, path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
     }
+
+    Ok(())
+}
+
+/// The content of a save file, or the default if there is none. A file which can't be read, such
+/// as one with a typo from a hand edit, is moved aside to `<name>.bak` rather than overwritten by
+/// the next save. If it can't be moved the directory isn't writable, and neither is the save.
+pub(crate) fn read_or_set_aside<T: Default>(read: io::Result<T>, path: &Path) -> T {
+    let err = match read {
+        Ok(value) => return value,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(err) => err,
+    };
+
+    let mut extension = path.extension().unwrap_or_default().to_owned();
+    extension.push(".bak");
+    let backup_path = path.with_extension(extension);
+
+    match fs::rename(path, &backup_path) {
+        Ok(()) => tracing::warn!(
+            "Unable to read {}, moved it to {}: {}",
+            path.to_string_lossy(),
+            backup_path.to_string_lossy(),
+            err
+        ),
+        Err(rename_err) => tracing::error!(
+            "Unable to read {}: {}, and unable to move it aside: {}",
+            path.to_string_lossy(),
+            err,
+            rename_err
+        ),
+    }
+
+    T::default()
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> io::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(&file, value)?;
+    // The data must be on disk before the rename is
+    file.sync_all()
+}
+
+fn read_save() -> io::Result<HashMap<String, SavedOutput>> {
+    parse_save(File::open(save_path())?)
+}
+
+fn parse_save(reader: impl Read) -> io::Result<HashMap<String, SavedOutput>> {
+    let outputs: HashMap<String, SavedOutputCompat> = serde_json::from_reader(reader)?;
+
+    Ok(outputs
+        .into_iter()
+        .map(|(screen, output)| (screen, output.into()))
+        .collect())
+}
+
+pub(crate) fn save_path() -> PathBuf {
+    config_dir().join("wallpapers.conf")
+}
+
+fn read_profiles() -> io::Result<BTreeMap<String, HashMap<String, SavedOutput>>> {
+    let file = File::open(profiles_path())?;
+    Ok(serde_json::from_reader(file)?)
 }
 
-fn save_dir() -> PathBuf {
+fn profiles_path() -> PathBuf {
+    config_dir().join("profiles.conf")
+}
+
+/// `$XDG_CONFIG_HOME/waypaper_engine`, created if missing. A directory which can't be created is
+/// only reported, writing the files in it then fails with the reason.
+pub(crate) fn config_dir() -> PathBuf {
     let base_dir = if let Ok(config) = env::var("XDG_CONFIG_HOME") {
         PathBuf::from(config)
     } else {
-        PathBuf::from(env::var("HOME").expect("Unable to find directory")).join(".config")
+        PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config")
     };
 
-    let parent = base_dir.join("waypaper_engine");
-    if !parent.exists() {
-        fs::create_dir_all(&parent).expect("Unable to create save directory");
+    let dir = base_dir.join("waypaper_engine");
+    if !dir.exists()
+        && let Err(err) = fs::create_dir_all(&dir)
+    {
+        tracing::warn!("Unable to create {}: {}", dir.to_string_lossy(), err);
+    }
+
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn unreadable_saves_are_set_aside() {
+        let dir = env::temp_dir().join(format!("waypaper_engine_saves_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wallpapers.conf");
+
+        let read = |path: &Path| -> io::Result<HashMap<String, u64>> {
+            Ok(serde_json::from_reader(File::open(path)?)?)
+        };
+
+        // No save yet
+        assert!(read_or_set_aside(read(&path), &path).is_empty());
+        assert!(!dir.join("wallpapers.conf.bak").exists());
+
+        fs::write(&path, r#"{"DP-1": 123}"#).unwrap();
+        assert_eq!(read_or_set_aside(read(&path), &path)["DP-1"], 123);
+        assert!(path.exists());
+
+        fs::write(&path, r#"{"DP-1": 123,}"#).unwrap();
+        assert!(read_or_set_aside(read(&path), &path).is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("wallpapers.conf.bak")).unwrap(),
+            r#"{"DP-1": 123,}"#
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    parent.join("wallpapers.conf")
+    fn wallpaper(id: u64) -> SavedOutput {
+        SavedOutput {
+            wallpaper: Some(SavedWallpaper::Workshop(id)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn legacy_saves() {
+        let outputs =
+            parse_save(r#"{"DP-1": 123, "HDMI-A-1": "/home/me/waves.mp4"}"#.as_bytes()).unwrap();
+
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs["DP-1"], wallpaper(123));
+        assert_eq!(
+            outputs["HDMI-A-1"].wallpaper,
+            Some(SavedWallpaper::Path(PathBuf::from("/home/me/waves.mp4")))
+        );
+    }
+
+    #[test]
+    fn mixed_saves() {
+        // Outputs saved after the format changed sit next to the ones which weren't touched since
+        let outputs = parse_save(
+            r#"{
+                "DP-1": 123,
+                "Dell U2720Q 8ZJ4T53": {
+                    "wallpaper": 456,
+                    "properties": {"rate": "2"},
+                    "scaling": "fit",
+                    "fps_cap": 30
+                },
+                "eDP-1": {"scaling": "stretch"}
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(outputs["DP-1"], wallpaper(123));
+        assert_eq!(
+            outputs["Dell U2720Q 8ZJ4T53"],
+            SavedOutput {
+                wallpaper: Some(SavedWallpaper::Workshop(456)),
+                properties: HashMap::from([("rate".to_owned(), "2".to_owned())]),
+                scaling: Some(ScalingMode::Fit),
+                fps_cap: Some(30),
+            }
+        );
+        assert_eq!(
+            outputs["eDP-1"],
+            SavedOutput {
+                scaling: Some(ScalingMode::Stretch),
+                ..Default::default()
+            }
+        );
+
+        assert!(parse_save(r#"{"DP-1": true}"#.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn outputs_migrate_to_their_monitor() {
+        let monitor = "Dell U2720Q 8ZJ4T53";
+        let mut outputs = HashMap::from([("DP-1".to_owned(), wallpaper(123))]);
+
+        assert!(migrate_output(&mut outputs, "DP-1", monitor));
+        assert_eq!(
+            outputs,
+            HashMap::from([(monitor.to_owned(), wallpaper(123))])
+        );
+
+        // Nothing left to move
+        assert!(!migrate_output(&mut outputs, "DP-1", monitor));
+        assert_eq!(outputs.len(), 1);
+    }
+
+    #[test]
+    fn monitor_settings_win_over_the_output_ones() {
+        let monitor = "Dell U2720Q 8ZJ4T53";
+        let mut outputs = HashMap::from([
+            ("DP-1".to_owned(), wallpaper(123)),
+            (monitor.to_owned(), wallpaper(456)),
+        ]);
+
+        // The monitor settings are newer, the output ones are left for whatever monitor is
+        // plugged there next
+        assert!(!migrate_output(&mut outputs, "DP-1", monitor));
+        assert_eq!(outputs["DP-1"], wallpaper(123));
+        assert_eq!(outputs[monitor], wallpaper(456));
+    }
 }
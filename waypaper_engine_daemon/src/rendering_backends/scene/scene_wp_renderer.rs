@@ -1,6 +1,8 @@
+use anyhow::{Context, anyhow};
+use waypaper_engine_formats::scene_package::{FileContent, ScenePackage};
+use waypaper_engine_formats::tex_file::TexFile;
+
 use crate::rendering_backends::scene::scene_structs::{Material, Model, ObjectValue, Scene};
-use crate::scene_package::ScenePackage;
-use crate::tex_file::TexFile;
 use crate::wallpaper_renderer::{SceneRenderingBackend, WPRendererImpl};
 
 pub(crate) struct SceneWPRenderer {
@@ -40,43 +42,52 @@ impl WPRendererImpl for SceneWPRenderer {
 }
 
 impl SceneRenderingBackend for SceneWPRenderer {
-    fn setup_scene_wallpaper(&mut self, scene_package: &ScenePackage) {
-        let scene_json = scene_package
-            .get_file("scene.json")
-            .expect("Couldn't find scene.json file");
+    fn setup_scene_wallpaper(&mut self, scene_package: &ScenePackage) -> anyhow::Result<()> {
+        let scene_json = get_package_file(scene_package, "scene.json")?;
         let scene: Scene =
-            serde_json::from_slice(scene_json.bytes()).expect("Couldn't parse scene.json");
+            serde_json::from_slice(scene_json.bytes()).context("Couldn't parse scene.json")?;
 
         let image = scene
             .objects
             .iter()
             .find(|x| matches!(x.value, ObjectValue::Image { .. }))
-            .unwrap();
+            .ok_or_else(|| anyhow!("scene.json does not contain any image object"))?;
 
         tracing::info!("found image : {}", image.name);
         if let ObjectValue::Image { image, .. } = &image.value {
             tracing::info!("Found model : {}", image);
             let model: Model =
-                serde_json::from_slice(scene_package.contents.get(image).unwrap().bytes()).unwrap();
+                serde_json::from_slice(get_package_file(scene_package, image)?.bytes())
+                    .with_context(|| format!("Couldn't parse {image}"))?;
 
             tracing::info!("Found material : {}", model.material);
-            let material: Material = serde_json::from_slice(
-                scene_package.contents.get(&model.material).unwrap().bytes(),
-            )
-            .unwrap();
+            let material: Material =
+                serde_json::from_slice(get_package_file(scene_package, &model.material)?.bytes())
+                    .with_context(|| format!("Couldn't parse {}", model.material))?;
 
-            let first_pass = material.passes.first().unwrap();
-            let first_texture = first_pass.textures.first().unwrap();
+            let first_texture = material
+                .passes
+                .first()
+                .and_then(|pass| pass.textures.first())
+                .ok_or_else(|| anyhow!("{} does not reference any texture", model.material))?;
+            let texture_path = "materials/".to_owned() + first_texture + ".tex";
             let texture = TexFile::from_bytes(Vec::from(
-                scene_package
-                    .contents
-                    .get(&("materials/".to_owned() + first_texture + ".tex"))
-                    .unwrap()
-                    .bytes(),
+                get_package_file(scene_package, &texture_path)?.bytes(),
             ))
-            .unwrap();
+            .with_context(|| format!("Couldn't read texture {texture_path}"))?;
             tracing::debug!("{:?}", scene);
             self.render_context = Some(RenderContext { scene, texture });
         }
+
+        Ok(())
     }
 }
+
+fn get_package_file<'a>(
+    scene_package: &'a ScenePackage,
+    name: &str,
+) -> anyhow::Result<&'a FileContent> {
+    scene_package
+        .get_file(name)
+        .ok_or_else(|| anyhow!("missing {name} in scene.pkg"))
+}
@@ -3,8 +3,9 @@ mod deinterlacer;
 mod demuxer;
 mod frame_pool;
 mod frames;
-mod gl;
+pub(crate) mod gl;
 mod pipeline;
+mod scaling;
 mod utils;
-mod video_backend_consts;
+pub(crate) mod video_backend_consts;
 pub(crate) mod video_wp_renderer;
@@ -1,6 +1,6 @@
 use crate::rendering_backends::video::deinterlacer::Deinterlacer;
 use crate::rendering_backends::video::utils;
-use anyhow::anyhow;
+use anyhow::{Context, anyhow};
 use ffmpeg_next::Rational;
 use ffmpeg_next::error::EAGAIN;
 use ffmpeg_next::ffi::av_frame_copy_props;
@@ -18,8 +18,12 @@ pub struct VideoDecoder {
 
 impl VideoDecoder {
     pub fn new(stream: &Stream) -> anyhow::Result<Self> {
+        let codec_id = stream.parameters().id();
         let context_decoder = codec::Context::from_parameters(stream.parameters())?;
-        let mut decoder = context_decoder.decoder().video()?;
+        let mut decoder = context_decoder
+            .decoder()
+            .video()
+            .with_context(|| format!("ffmpeg: unsupported codec {codec_id:?}").to_lowercase())?;
 
         if decoder.format() == Pixel::None || decoder.width() == 0 || decoder.height() == 0 {
             return Err(anyhow!(
@@ -96,6 +100,17 @@ impl VideoDecoder {
         (self.decoder.width(), self.decoder.height())
     }
 
+    /// Width of a pixel relative to its height, 1 when the stream doesn't specify it
+    pub fn sample_aspect_ratio(&self) -> f32 {
+        let aspect_ratio = self.decoder.aspect_ratio();
+
+        if aspect_ratio.numerator() > 0 && aspect_ratio.denominator() > 0 {
+            aspect_ratio.numerator() as f32 / aspect_ratio.denominator() as f32
+        } else {
+            1.0
+        }
+    }
+
     pub fn feed(&mut self, mut packet: Packet) -> anyhow::Result<()> {
         // Rescale the packet timestamps to the decoder's time base before decoding
         packet.rescale_ts(self.stream_time_base, self.decoder.time_base());
@@ -217,6 +232,11 @@ impl VideoDecoder {
         tracing::info!("Decoder flushed");
     }
 
+    /// Makes a drained decoder accept packets again
+    pub(crate) fn reset(&mut self) {
+        self.decoder.flush();
+    }
+
     pub fn drain(&mut self) {
         tracing::info!("Draining decoder");
         self.decoder.send_eof().unwrap();
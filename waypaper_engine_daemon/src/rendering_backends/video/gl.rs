@@ -294,6 +294,32 @@ impl Shader {
             gl::UseProgram(0);
         }
     }
+
+    /// The program must be in use
+    pub fn set_uniform_vec2(&self, name: &str, value: (f32, f32)) {
+        unsafe {
+            gl::Uniform2f(self.uniform_location(name), value.0, value.1);
+        }
+    }
+
+    /// The program must be in use
+    pub fn set_uniform_f32(&self, name: &str, value: f32) {
+        unsafe {
+            gl::Uniform1f(self.uniform_location(name), value);
+        }
+    }
+
+    /// The program must be in use, also used to pick the texture unit of samplers
+    pub fn set_uniform_i32(&self, name: &str, value: i32) {
+        unsafe {
+            gl::Uniform1i(self.uniform_location(name), value);
+        }
+    }
+
+    fn uniform_location(&self, name: &str) -> GLint {
+        let c_name = CString::new(name).unwrap();
+        unsafe { gl::GetUniformLocation(self.id, c_name.as_ptr()) }
+    }
 }
 
 impl Drop for Shader {
@@ -304,3 +330,100 @@ impl Drop for Shader {
         }
     }
 }
+
+/// Framebuffer rendering into a texture, to draw offscreen and sample the result afterwards
+pub struct Framebuffer {
+    id: u32,
+    texture: u32,
+    size: (u32, u32),
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut id = 0;
+        let mut texture = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as GLint,
+                width as GLint,
+                height as GLint,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, &mut id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture,
+                0,
+            );
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                tracing::error!("Framebuffer {} is incomplete (status: {:#x})", id, status);
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        Self {
+            id,
+            texture,
+            size: (width, height),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn texture(&self) -> u32 {
+        self.texture
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    /// Draws to the window surface again
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            tracing::debug!("Deleting framebuffer with id: {}", self.id);
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
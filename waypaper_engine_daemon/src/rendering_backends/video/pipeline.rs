@@ -2,13 +2,12 @@ use crate::rendering_backends::video::decoder::VideoDecoder;
 use crate::rendering_backends::video::demuxer::{Demuxer, Packet};
 use crate::rendering_backends::video::frame_pool::FramePool;
 use crate::rendering_backends::video::frames::{OrderedFramesContainer, TimedVideoFrame};
-use crate::rendering_backends::video::video_backend_consts::{
-    FRAME_POOL_SIZE, THREAD_FRAME_BUFFER_SIZE,
-};
+use anyhow::{Context, anyhow};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use waypaper_engine_shared::config::VideoConfig;
 
 // TODO: remove these arc mutexes and use channels instead
 pub struct DecodingPipeline {
@@ -16,31 +15,41 @@ pub struct DecodingPipeline {
     video_decoder: Arc<Mutex<VideoDecoder>>,
     pub(crate) decoding_thread: Option<thread::JoinHandle<()>>,
     shutdown_flag: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
     pub(crate) frames: Arc<Mutex<OrderedFramesContainer<TimedVideoFrame>>>,
+    config: VideoConfig,
 }
 
 impl DecodingPipeline {
-    pub fn new(video_file: &Path) -> Self {
-        let demuxer = Demuxer::new(video_file).expect("Failed to create demuxer");
-        let video_stream = demuxer.video_stream().expect("Failed to find video stream");
-        let video_decoder =
-            VideoDecoder::new(&video_stream).expect("Failed to create video decoder");
-
-        Self {
+    pub fn new(video_file: &Path, config: VideoConfig) -> anyhow::Result<Self> {
+        let demuxer = Demuxer::new(video_file)
+            .with_context(|| format!("ffmpeg: unable to open {}", video_file.to_string_lossy()))?;
+        let video_stream = demuxer
+            .video_stream()
+            .ok_or_else(|| anyhow!("ffmpeg: no video stream found"))?;
+        let video_decoder = VideoDecoder::new(&video_stream)?;
+
+        Ok(Self {
             demuxer: Arc::new(Mutex::new(demuxer)),
             video_decoder: Arc::new(Mutex::new(video_decoder)),
             decoding_thread: None,
             shutdown_flag: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
             frames: Arc::new(Mutex::new(OrderedFramesContainer::with_capacity(
-                THREAD_FRAME_BUFFER_SIZE,
+                config.frame_buffer_size.get(),
             ))),
-        }
+            config,
+        })
     }
 
     pub fn decoder_size(&self) -> (u32, u32) {
         self.video_decoder.lock().unwrap().size()
     }
 
+    pub fn sample_aspect_ratio(&self) -> f32 {
+        self.video_decoder.lock().unwrap().sample_aspect_ratio()
+    }
+
     pub fn framerate(&self) -> f32 {
         let demuxer = self.demuxer.lock().unwrap();
         let video_stream = demuxer.video_stream().expect("No video stream found");
@@ -52,8 +61,11 @@ impl DecodingPipeline {
     pub fn start_decoding(&mut self) {
         let weak = Arc::downgrade(&self.frames);
         let shutdown_flag = Arc::clone(&self.shutdown_flag);
+        let paused = Arc::clone(&self.paused);
         let demuxer = Arc::clone(&self.demuxer);
         let video_decoder = Arc::clone(&self.video_decoder);
+        let frame_buffer_size = self.config.frame_buffer_size.get();
+        let frame_pool_size = self.config.frame_pool_size.get();
 
         self.decoding_thread = Some(thread::spawn(move || {
             let mut rewind_count: u32 = 0;
@@ -64,9 +76,15 @@ impl DecodingPipeline {
                 (width, height) = video_decoder.size();
             }
 
-            let mut frame_pool = FramePool::new(width as usize, height as usize, FRAME_POOL_SIZE);
+            let mut frame_pool = FramePool::new(width as usize, height as usize, frame_pool_size);
 
             'outer: while !shutdown_flag.load(Ordering::Relaxed) {
+                if paused.load(Ordering::Relaxed) {
+                    tracing::debug!("Playback paused, parking decoding thread");
+                    thread::park();
+                    continue; // Parking can end spuriously, check the flags again
+                }
+
                 let mut demuxer = demuxer.lock().unwrap();
                 let mut video_decoder = video_decoder.lock().unwrap();
 
@@ -144,8 +162,11 @@ impl DecodingPipeline {
 
                 while !shutdown_flag.load(Ordering::Relaxed) {
                     if let Some(strong) = weak.upgrade() {
-                        if strong.lock().unwrap().len() >= THREAD_FRAME_BUFFER_SIZE {
-                            tracing::debug!("Frames in queue >= 20, paused decoding");
+                        if strong.lock().unwrap().len() >= frame_buffer_size {
+                            tracing::debug!(
+                                "Frames in queue >= {}, paused decoding",
+                                frame_buffer_size
+                            );
                             thread::park();
                             tracing::debug!("Resumed decoding")
                         } else {
@@ -174,6 +195,26 @@ impl DecodingPipeline {
         }));
     }
 
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+
+        if !paused && let Some(thread) = self.decoding_thread.as_ref() {
+            thread.thread().unpark();
+        }
+    }
+
+    /// Starts decoding again after `stop_decoding`, from where it stopped
+    pub fn restart_decoding(&mut self) {
+        if self.decoding_thread.is_some() {
+            return;
+        }
+
+        // The stopped thread drained the decoder, which then refuses new packets
+        self.video_decoder.lock().unwrap().reset();
+        self.shutdown_flag.store(false, Ordering::Relaxed);
+        self.start_decoding();
+    }
+
     pub fn stop_decoding(&mut self) {
         self.shutdown_flag.store(true, Ordering::Relaxed);
 
@@ -0,0 +1,77 @@
+use waypaper_engine_shared::ipc::ScalingMode;
+
+/// How the full-screen quad from `VERTEX_DATA` is transformed to honor a `ScalingMode`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct QuadTransform {
+    /// Quad size relative to the output, in normalized device coordinates
+    pub(crate) scale: (f32, f32),
+    pub(crate) tex_scale: (f32, f32),
+    pub(crate) tex_offset: (f32, f32),
+}
+
+impl QuadTransform {
+    const IDENTITY: QuadTransform = QuadTransform {
+        scale: (1.0, 1.0),
+        tex_scale: (1.0, 1.0),
+        tex_offset: (0.0, 0.0),
+    };
+
+    /// `video_size` is the size of the decoded frames, `sample_aspect_ratio` the width of a video
+    /// pixel relative to its height
+    pub(crate) fn new(
+        mode: ScalingMode,
+        video_size: (u32, u32),
+        sample_aspect_ratio: f32,
+        output_size: (u32, u32),
+    ) -> Self {
+        if video_size.0 == 0 || video_size.1 == 0 || output_size.0 == 0 || output_size.1 == 0 {
+            return Self::IDENTITY;
+        }
+
+        // Size of the video once displayed with square pixels
+        let video_width = video_size.0 as f32 * sample_aspect_ratio;
+        let video_height = video_size.1 as f32;
+        let (output_width, output_height) = (output_size.0 as f32, output_size.1 as f32);
+
+        let width_ratio = output_width / video_width;
+        let height_ratio = output_height / video_height;
+
+        match mode {
+            ScalingMode::Stretch => Self::IDENTITY,
+            ScalingMode::Fit => {
+                let ratio = width_ratio.min(height_ratio);
+
+                QuadTransform {
+                    scale: (
+                        video_width * ratio / output_width,
+                        video_height * ratio / output_height,
+                    ),
+                    ..Self::IDENTITY
+                }
+            }
+            ScalingMode::Fill => {
+                // Keep the quad full-screen and crop the texture instead
+                let ratio = width_ratio.max(height_ratio);
+                let tex_scale = (
+                    output_width / (video_width * ratio),
+                    output_height / (video_height * ratio),
+                );
+
+                QuadTransform {
+                    tex_scale,
+                    tex_offset: ((1.0 - tex_scale.0) / 2.0, (1.0 - tex_scale.1) / 2.0),
+                    ..Self::IDENTITY
+                }
+            }
+            ScalingMode::Center => QuadTransform {
+                scale: (video_width / output_width, video_height / output_height),
+                ..Self::IDENTITY
+            },
+            ScalingMode::Tile => QuadTransform {
+                // Texture wrapping is set to GL_REPEAT
+                tex_scale: (width_ratio, height_ratio),
+                ..Self::IDENTITY
+            },
+        }
+    }
+}
@@ -1,8 +1,5 @@
 use gl::types::{GLfloat, GLint};
 
-pub(crate) const THREAD_FRAME_BUFFER_SIZE: usize = 20;
-pub(crate) const FRAME_POOL_SIZE: usize = 25;
-
 #[rustfmt::skip]
 pub(crate) const VERTEX_DATA: [GLfloat; 20] = [
      1.0,  1.0,  0.0,     1.0, 1.0, // position (x,y,z), texcoord (u,v)
@@ -23,12 +20,16 @@ pub(crate) const VERTEX_SHADER_SRC: &str = r#"
     layout (location = 0) in vec3 aPos;
     layout (location = 1) in vec2 aTexCoord;
 
+    uniform vec2 scale;
+    uniform vec2 tex_scale;
+    uniform vec2 tex_offset;
+
     out vec2 tex_coord;
 
     void main()
     {
-        gl_Position = vec4(aPos.x, -aPos.y, aPos.z, 1.0);
-        tex_coord = aTexCoord;
+        gl_Position = vec4(aPos.x * scale.x, -aPos.y * scale.y, aPos.z, 1.0);
+        tex_coord = aTexCoord * tex_scale + tex_offset;
     }
 "#;
 
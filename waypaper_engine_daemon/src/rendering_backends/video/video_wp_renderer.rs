@@ -4,6 +4,7 @@ use crate::rendering_backends::video::gl::{
     ElementBuffer, GLDataType, Shader, VertexArray, VertexAttribute, VertexBuffer,
 };
 use crate::rendering_backends::video::pipeline::DecodingPipeline;
+use crate::rendering_backends::video::scaling::QuadTransform;
 use crate::rendering_backends::video::utils::FrameArray;
 use crate::rendering_backends::video::video_backend_consts::{
     FRAGMENT_SHADER_SRC, INDICES, VERTEX_DATA, VERTEX_SHADER_SRC,
@@ -13,13 +14,27 @@ use gl::types::{GLfloat, GLint, GLsizei, GLuint};
 use std::ffi::c_void;
 use std::path::PathBuf;
 use std::ptr::null;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use waypaper_engine_shared::config::{MIN_FRAME_BUFFER_SIZE, VideoConfig};
+use waypaper_engine_shared::ipc::ScalingMode;
+use waypaper_engine_shared::project::PropertyValue;
+
+// Wallpaper Engine properties understood by the video backend
+const PLAYBACK_RATE_PROPERTY: &str = "rate";
+const SCHEME_COLOR_PROPERTY: &str = "schemecolor";
+
+// A null rate would stop the video forever, pausing is the way to do that
+const MIN_PLAYBACK_RATE: f32 = 0.01;
 
 pub struct VideoWPRenderer {
     render_context: Option<RenderContext>,
 
-    video_path: Option<PathBuf>,
+    decoding_pipeline: Option<DecodingPipeline>,
     started_playback: bool,
+
+    playback_rate: f32,
+    scheme_color: Option<(f32, f32, f32)>,
+    scaling: ScalingMode,
 }
 
 struct RenderContext {
@@ -33,6 +48,7 @@ struct RenderData {
 
     framerate: f32,
     size: (u32, u32),
+    sample_aspect_ratio: f32,
 
     last_frame_time: Instant,
     last_frame: Option<FramePoolHandle>,
@@ -44,16 +60,28 @@ impl VideoWPRenderer {
     pub(crate) fn new() -> Self {
         Self {
             render_context: None,
-            video_path: None,
+            decoding_pipeline: None,
             started_playback: false,
+            playback_rate: 1.0,
+            scheme_color: None,
+            scaling: ScalingMode::default(),
         }
     }
 
+    /// Seconds between two frames at the given playback rate
+    fn frame_interval(playback_rate: f32, data: &RenderData) -> f32 {
+        1.0 / (data.framerate * playback_rate)
+    }
+
     fn start_playback(&mut self) {
-        let mut decoding_pipeline = DecodingPipeline::new(&self.video_path.clone().unwrap());
+        let mut decoding_pipeline = self
+            .decoding_pipeline
+            .take()
+            .expect("Playback started without a decoding pipeline");
 
         let size = decoding_pipeline.decoder_size();
         let framerate = decoding_pipeline.framerate();
+        let sample_aspect_ratio = decoding_pipeline.sample_aspect_ratio();
         decoding_pipeline.start_decoding();
 
         let ctx = self.render_context.as_mut().unwrap();
@@ -79,61 +107,17 @@ impl VideoWPRenderer {
                 texture,
                 framerate,
                 size,
+                sample_aspect_ratio,
                 last_frame_time: Instant::now(),
                 last_frame: None,
                 decoding_pipeline,
             });
         }
     }
-}
-
-impl VideoRenderingBackend for VideoWPRenderer {
-    fn setup_video_wallpaper(&mut self, video_path: PathBuf) {
-        tracing::info!("Setup video_rs wp");
 
-        self.video_path = Some(video_path);
-        self.started_playback = false;
-    }
-}
-
-impl WPRendererImpl for VideoWPRenderer {
-    fn init_render(&mut self) {
-        let ebo = ElementBuffer::new(&INDICES);
-        let mut vao = VertexArray::new(ebo);
-        let mut vbo = VertexBuffer::new(&VERTEX_DATA);
-
-        vbo.add_vertex_attribute(VertexAttribute {
-            index: 0,
-            size: 3,
-            data_type: GLDataType::Float,
-            normalized: false,
-            stride: (5 * size_of::<GLfloat>()) as GLint,
-            offset: 0,
-        });
-
-        vbo.add_vertex_attribute(VertexAttribute {
-            index: 1,
-            size: 2,
-            data_type: GLDataType::Float,
-            normalized: false,
-            stride: (5 * size_of::<GLfloat>()) as GLint,
-            offset: 3 * size_of::<GLfloat>(),
-        });
-
-        vao.bind();
-        vao.bind_vertex_buffer(vbo);
-        vao.unbind();
-
-        let shader = Shader::new(VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC);
-
-        self.render_context = Some(RenderContext {
-            shader,
-            vao,
-            data: None,
-        })
-    }
-
-    fn render(&mut self, width: u32, height: u32) {
+    /// Renders the next video frame if it is time to, the last one otherwise.
+    /// `advance` set to false always renders the last frame again.
+    fn draw(&mut self, width: u32, height: u32, advance: bool) {
         if !self.started_playback {
             self.start_playback();
             self.started_playback = true;
@@ -142,10 +126,11 @@ impl WPRendererImpl for VideoWPRenderer {
         let ctx = self.render_context.as_mut().unwrap();
         let data = ctx.data.as_mut().unwrap();
 
-        let frame = if Instant::now()
-            .duration_since(data.last_frame_time)
-            .as_secs_f32()
-            < 1.0 / data.framerate
+        let frame = if !advance
+            || Instant::now()
+                .duration_since(data.last_frame_time)
+                .as_secs_f32()
+                < Self::frame_interval(self.playback_rate, data)
         {
             tracing::debug!("Not enough time since last frame, rendering last frame again");
             match data.last_frame.as_ref() {
@@ -161,7 +146,7 @@ impl WPRendererImpl for VideoWPRenderer {
             // TODO: make this less bad
             let mut frames = data.decoding_pipeline.frames.lock().unwrap();
 
-            if frames.is_empty() || frames.len() < 16 {
+            if frames.is_empty() || frames.len() < MIN_FRAME_BUFFER_SIZE {
                 tracing::debug!("Not enough frames in queue, rendering last frame");
                 match data.last_frame.as_ref() {
                     Some(frame) => frame,
@@ -177,12 +162,9 @@ impl WPRendererImpl for VideoWPRenderer {
                     frame,
                 } = frames.pop().unwrap();
                 // TODO: remove this unpark when we have a better way to handle frame rendering
-                data.decoding_pipeline
-                    .decoding_thread
-                    .as_ref()
-                    .unwrap()
-                    .thread()
-                    .unpark();
+                if let Some(thread) = data.decoding_pipeline.decoding_thread.as_ref() {
+                    thread.thread().unpark();
+                }
 
                 tracing::info!(
                     "Rendering new frame, frames in queue: {}, rewind count: {}, frame time: {:?}",
@@ -200,6 +182,19 @@ impl WPRendererImpl for VideoWPRenderer {
             gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
 
             ctx.shader.use_program();
+
+            let transform = QuadTransform::new(
+                self.scaling,
+                data.size,
+                data.sample_aspect_ratio,
+                (width, height),
+            );
+            ctx.shader.set_uniform_vec2("scale", transform.scale);
+            ctx.shader
+                .set_uniform_vec2("tex_scale", transform.tex_scale);
+            ctx.shader
+                .set_uniform_vec2("tex_offset", transform.tex_offset);
+
             ctx.vao.bind();
 
             gl::BindTexture(gl::TEXTURE_2D, data.texture);
@@ -229,6 +224,132 @@ impl WPRendererImpl for VideoWPRenderer {
     }
 }
 
+impl VideoRenderingBackend for VideoWPRenderer {
+    fn setup_video_wallpaper(
+        &mut self,
+        video_path: PathBuf,
+        config: VideoConfig,
+    ) -> anyhow::Result<()> {
+        tracing::info!("Setup video_rs wp");
+
+        self.decoding_pipeline = Some(DecodingPipeline::new(&video_path, config)?);
+        self.started_playback = false;
+        self.playback_rate = 1.0;
+        self.scheme_color = None;
+
+        Ok(())
+    }
+}
+
+impl WPRendererImpl for VideoWPRenderer {
+    fn init_render(&mut self) {
+        let ebo = ElementBuffer::new(&INDICES);
+        let mut vao = VertexArray::new(ebo);
+        let mut vbo = VertexBuffer::new(&VERTEX_DATA);
+
+        vbo.add_vertex_attribute(VertexAttribute {
+            index: 0,
+            size: 3,
+            data_type: GLDataType::Float,
+            normalized: false,
+            stride: (5 * size_of::<GLfloat>()) as GLint,
+            offset: 0,
+        });
+
+        vbo.add_vertex_attribute(VertexAttribute {
+            index: 1,
+            size: 2,
+            data_type: GLDataType::Float,
+            normalized: false,
+            stride: (5 * size_of::<GLfloat>()) as GLint,
+            offset: 3 * size_of::<GLfloat>(),
+        });
+
+        vao.bind();
+        vao.bind_vertex_buffer(vbo);
+        vao.unbind();
+
+        let shader = Shader::new(VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC);
+
+        self.render_context = Some(RenderContext {
+            shader,
+            vao,
+            data: None,
+        })
+    }
+
+    fn render(&mut self, width: u32, height: u32) {
+        self.draw(width, height, true);
+    }
+
+    fn render_last_frame(&mut self, width: u32, height: u32) {
+        self.draw(width, height, false);
+    }
+
+    fn next_frame_time(&self) -> Option<Instant> {
+        let data = self.render_context.as_ref()?.data.as_ref()?;
+        let interval =
+            Duration::try_from_secs_f32(Self::frame_interval(self.playback_rate, data)).ok()?;
+
+        data.last_frame_time.checked_add(interval)
+    }
+
+    fn clear_color(&self) -> (f32, f32, f32) {
+        self.scheme_color.unwrap_or((0.0, 0.0, 0.0))
+    }
+
+    fn set_property(&mut self, key: &str, value: &PropertyValue) {
+        match (key, value) {
+            (PLAYBACK_RATE_PROPERTY, PropertyValue::Slider { value, .. }) => {
+                self.playback_rate = (*value as f32).max(MIN_PLAYBACK_RATE);
+            }
+            (SCHEME_COLOR_PROPERTY, PropertyValue::Color { r, g, b }) => {
+                self.scheme_color = Some((*r as f32, *g as f32, *b as f32));
+            }
+            _ => tracing::debug!("Ignoring unsupported video property {}", key),
+        }
+    }
+
+    fn set_scaling(&mut self, mode: ScalingMode) {
+        self.scaling = mode;
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        // A pipeline which hasn't started playback yet has no thread to park
+        if let Some(data) = self
+            .render_context
+            .as_mut()
+            .and_then(|ctx| ctx.data.as_mut())
+        {
+            data.decoding_pipeline.set_paused(paused);
+        }
+    }
+
+    fn set_static(&mut self, static_frame: bool) {
+        // No need to keep a decoding thread around until the animation resumes
+        if let Some(data) = self
+            .render_context
+            .as_mut()
+            .and_then(|ctx| ctx.data.as_mut())
+        {
+            if static_frame {
+                data.decoding_pipeline.stop_decoding();
+            } else {
+                data.decoding_pipeline.restart_decoding();
+            }
+        }
+    }
+
+    fn has_frame(&self) -> bool {
+        self.started_playback
+            && self
+                .render_context
+                .as_ref()
+                .and_then(|ctx| ctx.data.as_ref())
+                .is_some_and(|data| data.last_frame.is_some())
+    }
+}
+
 impl Drop for RenderData {
     fn drop(&mut self) {
         tracing::info!("Dropping RenderData");
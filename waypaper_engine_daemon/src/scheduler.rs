@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta};
+use glob::Pattern;
+use serde::Deserialize;
+use waypaper_engine_shared::ipc::ScheduledSwitch;
+
+use crate::profile_manager::{config_dir, write_atomically};
+use crate::solar::sun_times;
+
+// How often the wall clock is compared to the next planned switch
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// Drift between the wall clock and the monotonic clock over one check that is considered a jump,
+// e.g. after a suspend or a manual clock change
+const CLOCK_JUMP_THRESHOLD: TimeDelta = TimeDelta::seconds(5);
+
+/// Switches wallpapers at fixed times of the day or relative to sunrise and sunset, following the
+/// rules of `schedule.conf`
+pub struct Scheduler {
+    schedule: Schedule,
+    /// Occurrence of the rule last applied on each output, as a unix timestamp. Saved so that
+    /// restarting the daemon doesn't apply the active rules again over wallpapers set by hand.
+    applied: HashMap<String, i64>,
+    next_switch: Option<DateTime<Local>>,
+    /// Both clocks at the last check, compared to detect wall clock jumps
+    last_check: (Instant, DateTime<Local>),
+    /// Set when the outputs changed, so that new ones get their wallpaper right away
+    dirty: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Schedule {
+    location: Option<Location>,
+    /// Rules of each screen name or pattern
+    outputs: HashMap<String, Vec<ScheduleRule>>,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct Location {
+    /// Degrees, positive north of the equator
+    latitude: f64,
+    /// Degrees, positive east of Greenwich
+    longitude: f64,
+}
+
+#[derive(Clone, Deserialize)]
+struct ScheduleRule {
+    from: RuleTime,
+    wallpaper: u64,
+}
+
+/// When a rule starts applying: "HH:MM", "sunrise" or "sunset", the latter two optionally shifted
+/// with "+HH:MM" or "-HH:MM"
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+enum RuleTime {
+    At(NaiveTime),
+    Sunrise(TimeDelta),
+    Sunset(TimeDelta),
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        let schedule = match read_schedule() {
+            Ok(schedule) => schedule,
+            Err(err) => {
+                tracing::warn!(
+                    "Ignoring schedule {}: {}",
+                    save_path().to_string_lossy(),
+                    err
+                );
+                Schedule::default()
+            }
+        };
+
+        Scheduler {
+            schedule,
+            applied: read_applied(),
+            next_switch: None,
+            last_check: (Instant::now(), Local::now()),
+            dirty: true,
+        }
+    }
+
+    /// To be called when outputs are added, so that they get the wallpaper scheduled for them
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns whether a rule may have become active since the last evaluation
+    pub fn should_evaluate(&mut self) -> bool {
+        if self.schedule.outputs.is_empty() {
+            return false;
+        }
+
+        let now = Instant::now();
+        let (last_instant, last_time) = self.last_check;
+        if !self.dirty && now.duration_since(last_instant) < CHECK_INTERVAL {
+            return false;
+        }
+
+        let time = Local::now();
+        self.last_check = (now, time);
+
+        // The monotonic clock doesn't advance while suspended, unlike the wall clock
+        let elapsed = TimeDelta::from_std(now.duration_since(last_instant)).unwrap_or_default();
+        let drift = time - last_time - elapsed;
+        if drift.abs() > CLOCK_JUMP_THRESHOLD {
+            tracing::info!(
+                "Clock jumped by {}s, evaluating the schedule again",
+                drift.num_seconds()
+            );
+            return true;
+        }
+
+        self.dirty
+            || self
+                .next_switch
+                .is_some_and(|next_switch| time >= next_switch)
+    }
+
+    /// Returns the wallpapers to set on the given outputs, according to the rules active now
+    pub fn evaluate(&mut self, outputs: &[String]) -> Vec<(String, u64)> {
+        let now = Local::now();
+        let mut switches = vec![];
+
+        for screen in outputs {
+            let Some((time, rule)) = self
+                .schedule
+                .rules_for(screen)
+                .and_then(|rules| self.schedule.active_rule(rules, now))
+            else {
+                continue;
+            };
+
+            // Leave wallpapers set by hand alone until the next rule starts
+            if self.applied.get(screen) != Some(&time.timestamp()) {
+                self.applied.insert(screen.clone(), time.timestamp());
+                switches.push((screen.clone(), rule.wallpaper));
+            }
+        }
+
+        if !switches.is_empty()
+            && let Err(err) = write_atomically(&applied_path(), &self.applied)
+        {
+            tracing::warn!("Failed to save the applied schedule rules: {}", err);
+        }
+
+        self.next_switch = self
+            .schedule
+            .outputs
+            .values()
+            .filter_map(|rules| self.schedule.next_occurrences(rules, now).first().copied())
+            .map(|(time, _)| time)
+            .min();
+        self.dirty = false;
+
+        switches
+    }
+
+    /// Every switch planned in the next 24 hours, in chronological order
+    pub fn upcoming(&self) -> Vec<ScheduledSwitch> {
+        let now = Local::now();
+        let end = now + TimeDelta::days(1);
+
+        let mut switches: Vec<ScheduledSwitch> = self
+            .schedule
+            .outputs
+            .iter()
+            .flat_map(|(screen, rules)| {
+                self.schedule
+                    .next_occurrences(rules, now)
+                    .into_iter()
+                    .take_while(move |(time, _)| *time <= end)
+                    .map(|(time, rule)| ScheduledSwitch {
+                        screen: screen.clone(),
+                        wallpaper_id: rule.wallpaper,
+                        rule: rule.from.to_string(),
+                        timestamp: time.timestamp(),
+                    })
+            })
+            .collect();
+
+        switches.sort_by(|a, b| (a.timestamp, &a.screen).cmp(&(b.timestamp, &b.screen)));
+        switches
+    }
+}
+
+impl Schedule {
+    /// Rules of an output, rules written for its exact name win over patterns
+    fn rules_for(&self, screen: &str) -> Option<&[ScheduleRule]> {
+        if let Some(rules) = self.outputs.get(screen) {
+            return Some(rules);
+        }
+
+        let mut patterns: Vec<&String> = self.outputs.keys().collect();
+        patterns.sort();
+
+        patterns
+            .into_iter()
+            .find(|pattern| Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(screen)))
+            .map(|pattern| self.outputs[pattern].as_slice())
+    }
+
+    /// The rule which started last before `now`, with the time it started
+    fn active_rule<'a>(
+        &self,
+        rules: &'a [ScheduleRule],
+        now: DateTime<Local>,
+    ) -> Option<(DateTime<Local>, &'a ScheduleRule)> {
+        let today = now.date_naive();
+
+        [today.pred_opt(), Some(today)]
+            .into_iter()
+            .flatten()
+            .flat_map(|date| self.occurrences(rules, date))
+            .filter(|(time, _)| *time <= now)
+            .max_by_key(|(time, _)| *time)
+    }
+
+    /// Occurrences of the rules after `now`, in chronological order, up to the end of tomorrow
+    fn next_occurrences<'a>(
+        &self,
+        rules: &'a [ScheduleRule],
+        now: DateTime<Local>,
+    ) -> Vec<(DateTime<Local>, &'a ScheduleRule)> {
+        let today = now.date_naive();
+
+        let mut occurrences: Vec<_> = [Some(today), today.succ_opt()]
+            .into_iter()
+            .flatten()
+            .flat_map(|date| self.occurrences(rules, date))
+            .filter(|(time, _)| *time > now)
+            .collect();
+        occurrences.sort_by_key(|(time, _)| *time);
+
+        occurrences
+    }
+
+    fn occurrences<'a>(
+        &self,
+        rules: &'a [ScheduleRule],
+        date: NaiveDate,
+    ) -> Vec<(DateTime<Local>, &'a ScheduleRule)> {
+        rules
+            .iter()
+            .filter_map(|rule| Some((self.occurrence(rule.from, date)?, rule)))
+            .collect()
+    }
+
+    /// `None` when the time doesn't exist that day: skipped by a DST change, or no sunrise or
+    /// sunset during polar days and nights
+    fn occurrence(&self, time: RuleTime, date: NaiveDate) -> Option<DateTime<Local>> {
+        match time {
+            RuleTime::At(time) => date.and_time(time).and_local_timezone(Local).earliest(),
+            RuleTime::Sunrise(offset) | RuleTime::Sunset(offset) => {
+                let location = self.location?;
+                let sun_times = sun_times(date, location.latitude, location.longitude)?;
+                let event = if matches!(time, RuleTime::Sunrise(_)) {
+                    sun_times.sunrise
+                } else {
+                    sun_times.sunset
+                };
+
+                Some(event.with_timezone(&Local) + offset)
+            }
+        }
+    }
+
+    /// Drops what can never apply, so that mistakes are reported once when loading
+    fn validate(&mut self) -> Result<(), String> {
+        if let Some(location) = self.location
+            && (!(-90.0..=90.0).contains(&location.latitude)
+                || !(-180.0..=180.0).contains(&location.longitude))
+        {
+            return Err(format!(
+                "invalid location {}, {}: latitude must be within -90..90 and longitude within -180..180",
+                location.latitude, location.longitude
+            ));
+        }
+
+        let has_location = self.location.is_some();
+        self.outputs.retain(|screen, rules| {
+            if let Err(err) = Pattern::new(screen) {
+                tracing::warn!(
+                    "Ignoring schedule of invalid screen pattern [{}]: {}",
+                    screen,
+                    err
+                );
+                return false;
+            }
+
+            rules.retain(|rule| {
+                let solar = !matches!(rule.from, RuleTime::At(_));
+                if solar && !has_location {
+                    tracing::warn!(
+                        "Ignoring schedule rule [{}] of [{}]: no location is configured",
+                        rule.from,
+                        screen
+                    );
+                }
+                !solar || has_location
+            });
+
+            !rules.is_empty()
+        });
+
+        Ok(())
+    }
+}
+
+impl FromStr for RuleTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+
+        let (event, offset): (fn(TimeDelta) -> RuleTime, &str) =
+            if let Some(offset) = s.strip_prefix("sunrise") {
+                (RuleTime::Sunrise, offset)
+            } else if let Some(offset) = s.strip_prefix("sunset") {
+                (RuleTime::Sunset, offset)
+            } else {
+                return NaiveTime::parse_from_str(&s, "%H:%M")
+                    .map(RuleTime::At)
+                    .map_err(|_| invalid_rule_time(&s));
+            };
+
+        if offset.is_empty() {
+            return Ok(event(TimeDelta::zero()));
+        }
+
+        let parse_duration = |duration| {
+            NaiveTime::parse_from_str(duration, "%H:%M")
+                .map(|duration| duration.signed_duration_since(NaiveTime::MIN))
+                .map_err(|_| invalid_rule_time(&s))
+        };
+
+        if let Some(duration) = offset.strip_prefix('+') {
+            Ok(event(parse_duration(duration)?))
+        } else if let Some(duration) = offset.strip_prefix('-') {
+            Ok(event(-parse_duration(duration)?))
+        } else {
+            Err(invalid_rule_time(&s))
+        }
+    }
+}
+
+impl TryFrom<String> for RuleTime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for RuleTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (name, offset) = match self {
+            RuleTime::At(time) => return write!(f, "{}", time.format("%H:%M")),
+            RuleTime::Sunrise(offset) => ("sunrise", offset),
+            RuleTime::Sunset(offset) => ("sunset", offset),
+        };
+
+        write!(f, "{}", name)?;
+        if !offset.is_zero() {
+            let minutes = offset.num_minutes().abs();
+            write!(
+                f,
+                "{}{:02}:{:02}",
+                if *offset < TimeDelta::zero() {
+                    "-"
+                } else {
+                    "+"
+                },
+                minutes / 60,
+                minutes % 60
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_rule_time(time: &str) -> String {
+    format!(
+        "invalid time \"{}\", expected HH:MM, sunrise or sunset, the latter two optionally followed by +HH:MM or -HH:MM",
+        time
+    )
+}
+
+fn read_schedule() -> Result<Schedule, String> {
+    let file = match File::open(save_path()) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Schedule::default()),
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let mut schedule: Schedule = serde_json::from_reader(file).map_err(|err| err.to_string())?;
+    schedule.validate()?;
+
+    Ok(schedule)
+}
+
+fn save_path() -> PathBuf {
+    config_dir().join("schedule.conf")
+}
+
+fn read_applied() -> HashMap<String, i64> {
+    File::open(applied_path())
+        .ok()
+        .and_then(|file| serde_json::from_reader(file).ok())
+        .unwrap_or_default()
+}
+
+fn applied_path() -> PathBuf {
+    config_dir().join("schedule_state.conf")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn rule(from: &str, wallpaper: u64) -> ScheduleRule {
+        ScheduleRule {
+            from: from.parse().unwrap(),
+            wallpaper,
+        }
+    }
+
+    fn schedule(location: Option<Location>) -> Schedule {
+        Schedule {
+            location,
+            outputs: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn parse_rule_times() {
+        assert_eq!("07:30".parse(), Ok(RuleTime::At(time(7, 30))));
+        assert_eq!("sunrise".parse(), Ok(RuleTime::Sunrise(TimeDelta::zero())));
+        assert_eq!(
+            " Sunset+02:00 ".parse(),
+            Ok(RuleTime::Sunset(TimeDelta::hours(2)))
+        );
+        assert_eq!(
+            "sunrise-00:45".parse(),
+            Ok(RuleTime::Sunrise(-TimeDelta::minutes(45)))
+        );
+
+        for invalid in [
+            "",
+            "25:00",
+            "7h30",
+            "noon",
+            "sunset+2",
+            "sunset*01:00",
+            "sunrise-",
+        ] {
+            assert!(
+                invalid.parse::<RuleTime>().is_err(),
+                "{invalid:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn display_rule_times() {
+        for time in ["07:30", "sunrise", "sunset+02:00", "sunrise-00:45"] {
+            assert_eq!(time.parse::<RuleTime>().unwrap().to_string(), time);
+        }
+    }
+
+    #[test]
+    fn next_occurrences_span_today_and_tomorrow() {
+        let rules = [rule("19:00", 2), rule("07:00", 1)];
+        let occurrences: Vec<_> = schedule(None)
+            .next_occurrences(&rules, local(15, 12, 0))
+            .into_iter()
+            .map(|(time, rule)| (time, rule.wallpaper))
+            .collect();
+
+        assert_eq!(
+            occurrences,
+            [
+                (local(15, 19, 0), 2),
+                (local(16, 7, 0), 1),
+                (local(16, 19, 0), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn active_rule_started_yesterday() {
+        let rules = [rule("07:00", 1), rule("19:00", 2)];
+        let schedule = schedule(None);
+
+        let (time, rule) = schedule.active_rule(&rules, local(15, 6, 0)).unwrap();
+        assert_eq!((time, rule.wallpaper), (local(14, 19, 0), 2));
+
+        let (time, rule) = schedule.active_rule(&rules, local(15, 7, 0)).unwrap();
+        assert_eq!((time, rule.wallpaper), (local(15, 7, 0), 1));
+    }
+
+    #[test]
+    fn solar_rules() {
+        let rules = [rule("sunrise+01:00", 1), rule("sunset", 2)];
+        let paris = Location {
+            latitude: 48.8566,
+            longitude: 2.3522,
+        };
+
+        let occurrences = schedule(Some(paris)).next_occurrences(&rules, local(15, 0, 0));
+        assert_eq!(occurrences.len(), 4);
+        let sun_times = sun_times(local(15, 0, 0).date_naive(), 48.8566, 2.3522).unwrap();
+        assert_eq!(
+            occurrences[0].0,
+            sun_times.sunrise.with_timezone(&Local) + TimeDelta::hours(1)
+        );
+        assert_eq!(occurrences[1].0, sun_times.sunset.with_timezone(&Local));
+
+        // No location, or no sunrise nor sunset during the polar night
+        assert!(
+            schedule(None)
+                .next_occurrences(&rules, local(15, 0, 0))
+                .is_empty()
+        );
+        let tromso = Location {
+            latitude: 69.6496,
+            longitude: 18.956,
+        };
+        assert!(
+            schedule(Some(tromso))
+                .next_occurrences(&rules, local(1, 0, 0))
+                .is_empty()
+        );
+    }
+}
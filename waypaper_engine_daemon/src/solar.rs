@@ -0,0 +1,115 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+// Julian date of 2000-01-01 12:00 UTC
+const J2000: f64 = 2451545.0;
+// Julian date of the unix epoch
+const UNIX_EPOCH_JULIAN_DATE: f64 = 2440587.5;
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+// Apparent altitude of the sun center at sunrise and sunset, accounting for refraction and the
+// sun radius
+const SUN_ALTITUDE_DEGREES: f64 = -0.833;
+const EARTH_OBLIQUITY_DEGREES: f64 = 23.4397;
+
+/// Sunrise and sunset of a day
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SunTimes {
+    pub sunrise: DateTime<Utc>,
+    pub sunset: DateTime<Utc>,
+}
+
+/// Computes sunrise and sunset around the solar noon of `date` at the given location, using the
+/// sunrise equation. Precise to about a minute, which is plenty to switch wallpapers. Returns
+/// `None` during polar day and polar night.
+///
+/// `latitude` is positive north of the equator, `longitude` east of Greenwich, both in degrees.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> Option<SunTimes> {
+    // Julian date of the day start, 2000-01-01 being day 730120 of the common era
+    let julian_date = date.num_days_from_ce() as f64 - 730120.0 + J2000 - 0.5;
+
+    // Mean solar noon, in days since J2000
+    let days = (julian_date - J2000 + 0.0008).ceil() - longitude / 360.0;
+
+    let mean_anomaly = (357.5291 + 0.98560028 * days)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center = 1.9148 * mean_anomaly.sin()
+        + 0.02 * (2.0 * mean_anomaly).sin()
+        + 0.0003 * (3.0 * mean_anomaly).sin();
+    let ecliptic_longitude = (mean_anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let solar_transit =
+        J2000 + days + 0.0053 * mean_anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination_sin = ecliptic_longitude.sin() * EARTH_OBLIQUITY_DEGREES.to_radians().sin();
+    let declination_cos = declination_sin.asin().cos();
+    let latitude = latitude.to_radians();
+
+    let hour_angle_cos = (SUN_ALTITUDE_DEGREES.to_radians().sin()
+        - latitude.sin() * declination_sin)
+        / (latitude.cos() * declination_cos);
+    if !(-1.0..=1.0).contains(&hour_angle_cos) {
+        return None;
+    }
+
+    let hour_angle = hour_angle_cos.acos().to_degrees();
+
+    Some(SunTimes {
+        sunrise: julian_date_to_utc(solar_transit - hour_angle / 360.0)?,
+        sunset: julian_date_to_utc(solar_transit + hour_angle / 360.0)?,
+    })
+}
+
+fn julian_date_to_utc(julian_date: f64) -> Option<DateTime<Utc>> {
+    let timestamp = (julian_date - UNIX_EPOCH_JULIAN_DATE) * SECONDS_PER_DAY;
+    DateTime::from_timestamp(timestamp.round() as i64, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// The expected times are the ones published by almanacs, rounded to the minute
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let expected: DateTime<Utc> = expected.parse().unwrap();
+        assert!(
+            (actual - expected).num_minutes().abs() <= 2,
+            "{actual} is too far from {expected}"
+        );
+    }
+
+    #[test]
+    fn paris_summer_solstice() {
+        let times = sun_times(date(2024, 6, 21), 48.8566, 2.3522).unwrap();
+        assert_close(times.sunrise, "2024-06-21T03:47:00Z");
+        assert_close(times.sunset, "2024-06-21T19:58:00Z");
+    }
+
+    #[test]
+    fn new_york_winter_solstice() {
+        let times = sun_times(date(2024, 12, 21), 40.7128, -74.006).unwrap();
+        assert_close(times.sunrise, "2024-12-21T12:16:00Z");
+        assert_close(times.sunset, "2024-12-21T21:32:00Z");
+    }
+
+    #[test]
+    fn sydney_sunrise_is_the_previous_utc_day() {
+        let times = sun_times(date(2024, 12, 21), -33.8688, 151.2093).unwrap();
+        assert_close(times.sunrise, "2024-12-20T18:41:00Z");
+        assert_close(times.sunset, "2024-12-21T09:05:00Z");
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        // Tromsø
+        assert_eq!(sun_times(date(2024, 6, 21), 69.6496, 18.956), None);
+        assert_eq!(sun_times(date(2024, 12, 21), 69.6496, 18.956), None);
+        assert!(sun_times(date(2024, 3, 20), 69.6496, 18.956).is_some());
+    }
+}
@@ -0,0 +1,218 @@
+use std::ffi::c_void;
+use std::ptr::null;
+use std::time::Duration;
+
+use gl::types::{GLfloat, GLint, GLsizei};
+use waypaper_engine_shared::config::{TransitionConfig, TransitionKind};
+
+use crate::rendering_backends::video::gl::{
+    ElementBuffer, Framebuffer, GLDataType, Shader, VertexArray, VertexAttribute, VertexBuffer,
+};
+use crate::rendering_backends::video::video_backend_consts::{INDICES, VERTEX_DATA};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+}
+
+impl Transition {
+    /// `None` if the configuration asks for instant switches
+    pub fn from_config(config: &TransitionConfig) -> Option<Transition> {
+        (config.kind != TransitionKind::None && config.duration_ms > 0).then_some(Transition {
+            kind: config.kind,
+            duration: Duration::from_millis(config.duration_ms),
+        })
+    }
+}
+
+/// Value of the `kind` uniform of `FRAGMENT_SHADER_SRC`
+fn shader_id(kind: TransitionKind) -> i32 {
+    match kind {
+        TransitionKind::None | TransitionKind::Crossfade => 0,
+        TransitionKind::Slide => 1,
+        TransitionKind::Wipe => 2,
+        TransitionKind::Zoom => 3,
+    }
+}
+
+/// Offscreen targets the outgoing and incoming wallpapers are rendered to, and the shader
+/// blending them on the output
+pub(crate) struct TransitionCompositor {
+    shader: Shader,
+    vao: VertexArray,
+    from: Option<Framebuffer>,
+    to: Option<Framebuffer>,
+}
+
+impl TransitionCompositor {
+    /// An EGL context must be current
+    pub(crate) fn new() -> Self {
+        let ebo = ElementBuffer::new(&INDICES);
+        let mut vao = VertexArray::new(ebo);
+        let mut vbo = VertexBuffer::new(&VERTEX_DATA);
+
+        vbo.add_vertex_attribute(VertexAttribute {
+            index: 0,
+            size: 3,
+            data_type: GLDataType::Float,
+            normalized: false,
+            stride: (5 * size_of::<GLfloat>()) as GLint,
+            offset: 0,
+        });
+
+        vbo.add_vertex_attribute(VertexAttribute {
+            index: 1,
+            size: 2,
+            data_type: GLDataType::Float,
+            normalized: false,
+            stride: (5 * size_of::<GLfloat>()) as GLint,
+            offset: 3 * size_of::<GLfloat>(),
+        });
+
+        vao.bind();
+        vao.bind_vertex_buffer(vbo);
+        vao.unbind();
+
+        Self {
+            shader: Shader::new(VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC),
+            vao,
+            from: None,
+            to: None,
+        }
+    }
+
+    /// Runs `draw` with the outgoing wallpaper target bound, cleared with `clear_color`
+    pub(crate) fn draw_from(
+        &mut self,
+        width: u32,
+        height: u32,
+        clear_color: (f32, f32, f32),
+        draw: impl FnOnce(),
+    ) {
+        draw_offscreen(&mut self.from, width, height, clear_color, draw);
+    }
+
+    /// Runs `draw` with the incoming wallpaper target bound, cleared with `clear_color`
+    pub(crate) fn draw_to(
+        &mut self,
+        width: u32,
+        height: u32,
+        clear_color: (f32, f32, f32),
+        draw: impl FnOnce(),
+    ) {
+        draw_offscreen(&mut self.to, width, height, clear_color, draw);
+    }
+
+    /// Blends both targets on the output, `progress` going from 0 (outgoing wallpaper only) to 1
+    pub(crate) fn blend(&self, kind: TransitionKind, progress: f32, width: u32, height: u32) {
+        let (Some(from), Some(to)) = (self.from.as_ref(), self.to.as_ref()) else {
+            return;
+        };
+
+        unsafe {
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+
+            self.shader.use_program();
+            self.shader.set_uniform_i32("from_tex", 0);
+            self.shader.set_uniform_i32("to_tex", 1);
+            self.shader.set_uniform_i32("kind", shader_id(kind));
+            // Ease in and out
+            self.shader
+                .set_uniform_f32("progress", progress * progress * (3.0 - 2.0 * progress));
+
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, to.texture());
+            // Renderers expect the default texture unit
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, from.texture());
+
+            self.vao.bind();
+            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, null::<c_void>());
+            self.vao.unbind();
+
+            self.shader.unbind();
+        }
+    }
+}
+
+fn draw_offscreen(
+    target: &mut Option<Framebuffer>,
+    width: u32,
+    height: u32,
+    clear_color: (f32, f32, f32),
+    draw: impl FnOnce(),
+) {
+    // Outputs can be resized in the middle of a transition
+    if target
+        .as_ref()
+        .is_none_or(|target| target.size() != (width, height))
+    {
+        *target = Some(Framebuffer::new(width, height));
+    }
+    let target = target.as_ref().unwrap();
+
+    target.bind();
+    unsafe {
+        gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        gl::ClearColor(clear_color.0, clear_color.1, clear_color.2, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+
+    draw();
+
+    target.unbind();
+}
+
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 330 core
+
+    layout (location = 0) in vec3 aPos;
+    layout (location = 1) in vec2 aTexCoord;
+
+    out vec2 tex_coord;
+
+    void main()
+    {
+        gl_Position = vec4(aPos, 1.0);
+        tex_coord = aTexCoord;
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 330 core
+
+    uniform sampler2D from_tex;
+    uniform sampler2D to_tex;
+    uniform int kind;
+    uniform float progress;
+
+    in vec2 tex_coord;
+    out vec4 out_color;
+
+    const float WIPE_EDGE = 0.05;
+
+    void main()
+    {
+        if (kind == 1) {
+            // Slide
+            if (tex_coord.x < 1.0 - progress) {
+                out_color = texture(from_tex, tex_coord + vec2(progress, 0.0));
+            } else {
+                out_color = texture(to_tex, tex_coord - vec2(1.0 - progress, 0.0));
+            }
+        } else if (kind == 2) {
+            // Wipe, with a soft edge that starts and ends offscreen
+            float edge = progress * (1.0 + 2.0 * WIPE_EDGE) - WIPE_EDGE;
+            float from_weight = smoothstep(edge - WIPE_EDGE, edge + WIPE_EDGE, tex_coord.x);
+            out_color = mix(texture(to_tex, tex_coord), texture(from_tex, tex_coord), from_weight);
+        } else if (kind == 3) {
+            // Zoom
+            vec2 zoomed = 0.5 + (tex_coord - 0.5) / (1.0 + progress);
+            out_color = mix(texture(from_tex, zoomed), texture(to_tex, tex_coord), progress);
+        } else {
+            // Crossfade
+            out_color = mix(texture(from_tex, tex_coord), texture(to_tex, tex_coord), progress);
+        }
+    }
+"#;
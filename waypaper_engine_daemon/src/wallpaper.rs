@@ -1,10 +1,10 @@
-use std::error::Error;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use waypaper_engine_shared::project::{WEProject, WallpaperType};
+use anyhow::{Context, anyhow};
 
-use crate::scene_package::ScenePackage;
+use waypaper_engine_formats::scene_package::ScenePackage;
+use waypaper_engine_shared::project::{WEProject, WallpaperType};
 
 pub enum Wallpaper {
     Video {
@@ -24,15 +24,24 @@ pub enum Wallpaper {
 }
 
 impl Wallpaper {
-    pub fn new(path: PathBuf) -> Result<Wallpaper, Box<dyn Error>> {
-        let project = WEProject::new(
-            &path.join("project.json"),
-            u64::from_str(path.file_name().unwrap().to_str().unwrap()).unwrap(),
-        );
+    pub fn new(path: PathBuf) -> anyhow::Result<Wallpaper> {
+        // Workshop items are stored in a directory named after their id
+        let id = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| u64::from_str(name).ok());
+
+        let project_path = path.join("project.json");
+        let project = WEProject::new(&project_path, id)
+            .with_context(|| format!("Unable to read {}", project_path.to_string_lossy()))?;
 
         Ok(match project.wallpaper_type {
             WallpaperType::Video => {
-                tracing::debug!("{}", project.file.as_ref().unwrap());
+                let file = project
+                    .file
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("project.json does not reference any video file"))?;
+                tracing::debug!("{}", file);
 
                 Wallpaper::Video {
                     base_dir_path: path,
@@ -41,7 +50,9 @@ impl Wallpaper {
             }
             WallpaperType::Scene => {
                 let scene_pkg_path = path.join("scene.pkg");
-                let scene_package = ScenePackage::new(&scene_pkg_path).unwrap();
+                let scene_package = ScenePackage::new(&scene_pkg_path).with_context(|| {
+                    format!("Unable to unpack {}", scene_pkg_path.to_string_lossy())
+                })?;
 
                 Wallpaper::Scene {
                     project,
@@ -53,6 +64,24 @@ impl Wallpaper {
         })
     }
 
+    pub fn project(&self) -> &WEProject {
+        match self {
+            Wallpaper::Video { project, .. }
+            | Wallpaper::Scene { project, .. }
+            | Wallpaper::Web { project }
+            | Wallpaper::Preset { project } => project,
+        }
+    }
+
+    pub fn project_mut(&mut self) -> &mut WEProject {
+        match self {
+            Wallpaper::Video { project, .. }
+            | Wallpaper::Scene { project, .. }
+            | Wallpaper::Web { project }
+            | Wallpaper::Preset { project } => project,
+        }
+    }
+
     pub fn wp_type(&self) -> WallpaperType {
         match self {
             Wallpaper::Video { .. } => WallpaperType::Video,
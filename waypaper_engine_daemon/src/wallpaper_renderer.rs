@@ -1,20 +1,43 @@
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+use anyhow::{anyhow, bail};
 use smithay_client_toolkit::reexports::client::Connection;
 
 use crate::egl::EGLState;
 use crate::rendering_backends::scene::scene_wp_renderer::SceneWPRenderer;
 use crate::rendering_backends::video::video_wp_renderer::VideoWPRenderer;
-use crate::scene_package::ScenePackage;
+use crate::transition::{Transition, TransitionCompositor};
 use crate::wallpaper::Wallpaper;
+use waypaper_engine_formats::scene_package::ScenePackage;
+use waypaper_engine_shared::config::VideoConfig;
+use waypaper_engine_shared::ipc::ScalingMode;
+use waypaper_engine_shared::project::PropertyValue;
+
+// Longest wait for the new wallpaper first frame before starting the transition anyway
+const TRANSITION_MAX_WAIT: Duration = Duration::from_secs(2);
 
 pub struct WPRenderer {
     _connection: Rc<Connection>,
     _egl_state: Rc<EGLState>,
     renderer: Option<RenderingBackend>,
     renderer_initialized: bool,
+    scaling: ScalingMode,
+    transition: Option<Transition>,
+    video_config: VideoConfig,
+    /// Backend of the previous wallpaper, kept alive until the transition to the current one ends
+    outgoing: Option<OutgoingBackend>,
+    compositor: Option<TransitionCompositor>,
+}
+
+struct OutgoingBackend {
+    renderer: RenderingBackend,
+    transition: Transition,
+    created: Instant,
+    /// Set once the new wallpaper has something to show
+    started: Option<Instant>,
 }
 
 impl WPRenderer {
@@ -24,37 +47,81 @@ impl WPRenderer {
             _egl_state: egl_state,
             renderer: None,
             renderer_initialized: false,
+            scaling: ScalingMode::default(),
+            transition: None,
+            video_config: VideoConfig::default(),
+            outgoing: None,
+            compositor: None,
+        }
+    }
+
+    /// An EGL context must be current, the outgoing backend may be dropped
+    pub fn setup_wallpaper(&mut self, wallpaper: &Wallpaper) -> anyhow::Result<()> {
+        let transition = self.transition.filter(|_| self.renderer_initialized);
+
+        let Some(transition) = transition else {
+            return self.setup_backend(wallpaper);
+        };
+
+        // Both wallpapers are drawn during the transition, so the new one needs its own backend
+        let previous = self.renderer.take();
+        self.renderer_initialized = false;
+
+        if let Err(err) = self.setup_backend(wallpaper) {
+            self.renderer = previous;
+            self.renderer_initialized = true;
+            return Err(err);
         }
+
+        self.outgoing = previous.map(|renderer| OutgoingBackend {
+            renderer,
+            transition,
+            created: Instant::now(),
+            started: None,
+        });
+
+        Ok(())
     }
 
-    pub fn setup_wallpaper(&mut self, wallpaper: &Wallpaper) {
+    fn setup_backend(&mut self, wallpaper: &Wallpaper) -> anyhow::Result<()> {
         match wallpaper {
             Wallpaper::Video {
                 project,
                 base_dir_path,
             } => {
+                let video_path = base_dir_path.join(
+                    project
+                        .file
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("Video wallpaper without video file"))?,
+                );
+
                 if let Some(RenderingBackend::Video(video_renderer)) = &mut self.renderer {
-                    video_renderer
-                        .setup_video_wallpaper(base_dir_path.join(project.file.as_ref().unwrap()));
+                    video_renderer.setup_video_wallpaper(video_path, self.video_config)?;
                 } else {
                     let mut renderer = Box::new(VideoWPRenderer::new());
-                    renderer
-                        .setup_video_wallpaper(base_dir_path.join(project.file.as_ref().unwrap()));
+                    renderer.setup_video_wallpaper(video_path, self.video_config)?;
+                    renderer.set_scaling(self.scaling);
                     self.renderer = Some(RenderingBackend::Video(renderer));
+                    self.renderer_initialized = false;
                 }
             }
             Wallpaper::Scene { scene_package, .. } => {
                 if let Some(RenderingBackend::Scene(scene_renderer)) = &mut self.renderer {
-                    scene_renderer.setup_scene_wallpaper(scene_package);
+                    scene_renderer.setup_scene_wallpaper(scene_package)?;
                 } else {
                     let mut renderer = Box::new(SceneWPRenderer::new());
-                    renderer.setup_scene_wallpaper(scene_package);
+                    renderer.setup_scene_wallpaper(scene_package)?;
+                    renderer.set_scaling(self.scaling);
                     self.renderer = Some(RenderingBackend::Scene(renderer));
+                    self.renderer_initialized = false;
                 }
             }
-            Wallpaper::Web { .. } => todo!(),
-            Wallpaper::Preset { .. } => todo!(),
+            Wallpaper::Web { .. } => bail!("Web wallpapers are not supported yet"),
+            Wallpaper::Preset { .. } => bail!("Preset wallpapers are not supported yet"),
         }
+
+        Ok(())
     }
 
     pub(crate) fn clear_color(&self) -> (f32, f32, f32) {
@@ -66,13 +133,141 @@ impl WPRenderer {
     }
 
     pub(crate) fn render(&mut self, width: u32, height: u32) {
+        self.draw(width, height, true);
+    }
+
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        for renderer in self.renderers_mut() {
+            renderer.set_paused(paused);
+        }
+    }
+
+    pub(crate) fn set_static(&mut self, static_frame: bool) {
+        for renderer in self.renderers_mut() {
+            renderer.set_static(static_frame);
+        }
+    }
+
+    /// Stays `false` until the transition ends, so that it isn't frozen halfway
+    pub(crate) fn has_frame(&self) -> bool {
+        self.outgoing.is_none()
+            && self
+                .renderer
+                .as_ref()
+                .is_some_and(|renderer| renderer.has_frame())
+    }
+
+    /// Used by the next wallpaper changes, `None` switches instantly
+    pub(crate) fn set_transition(&mut self, transition: Option<Transition>) {
+        self.transition = transition;
+    }
+
+    /// Used by the next video wallpapers
+    pub(crate) fn set_video_config(&mut self, config: VideoConfig) {
+        self.video_config = config;
+    }
+
+    pub(crate) fn scaling(&self) -> ScalingMode {
+        self.scaling
+    }
+
+    pub(crate) fn set_scaling(&mut self, mode: ScalingMode) {
+        self.scaling = mode;
+
+        for renderer in self.renderers_mut() {
+            renderer.set_scaling(mode);
+        }
+    }
+
+    pub(crate) fn set_property(&mut self, key: &str, value: &PropertyValue) {
         if let Some(renderer) = self.renderer.as_mut() {
-            renderer.render(width, height);
-        } else {
+            renderer.set_property(key, value);
+        }
+    }
+
+    pub(crate) fn next_frame_time(&self) -> Option<Instant> {
+        // Transitions are animated at the output refresh rate
+        if self.outgoing.is_some() {
+            return None;
+        }
+
+        self.renderer
+            .as_ref()
+            .and_then(|renderer| renderer.next_frame_time())
+    }
+
+    pub(crate) fn render_last_frame(&mut self, width: u32, height: u32) {
+        self.draw(width, height, false);
+    }
+
+    /// `advance` set to false renders the last frame of each backend again, without making the
+    /// transition progress
+    fn draw(&mut self, width: u32, height: u32, advance: bool) {
+        let Some(renderer) = self.renderer.as_mut() else {
             unreachable!();
+        };
+
+        let Some(outgoing) = self.outgoing.as_mut() else {
+            if advance {
+                renderer.render(width, height);
+            } else {
+                renderer.render_last_frame(width, height);
+            }
+            return;
+        };
+
+        let compositor = self
+            .compositor
+            .get_or_insert_with(TransitionCompositor::new);
+
+        compositor.draw_from(width, height, outgoing.renderer.clear_color(), || {
+            if advance {
+                outgoing.renderer.render(width, height);
+            } else {
+                outgoing.renderer.render_last_frame(width, height);
+            }
+        });
+        compositor.draw_to(width, height, renderer.clear_color(), || {
+            if advance {
+                renderer.render(width, height);
+            } else {
+                renderer.render_last_frame(width, height);
+            }
+        });
+
+        // Don't fade to the clear color while the new wallpaper is still loading
+        if advance
+            && outgoing.started.is_none()
+            && (renderer.has_frame() || outgoing.created.elapsed() >= TRANSITION_MAX_WAIT)
+        {
+            outgoing.started = Some(Instant::now());
+        }
+
+        let progress = match outgoing.started {
+            Some(started) if !outgoing.transition.duration.is_zero() => {
+                started.elapsed().as_secs_f32() / outgoing.transition.duration.as_secs_f32()
+            }
+            Some(_) => 1.0,
+            None => 0.0,
+        };
+
+        compositor.blend(outgoing.transition.kind, progress.min(1.0), width, height);
+
+        if advance && progress >= 1.0 {
+            tracing::debug!("Transition ended, dropping the previous wallpaper renderer");
+            self.outgoing = None;
+            self.compositor = None;
         }
     }
 
+    fn renderers_mut(&mut self) -> impl Iterator<Item = &mut RenderingBackend> {
+        self.renderer.iter_mut().chain(
+            self.outgoing
+                .iter_mut()
+                .map(|outgoing| &mut outgoing.renderer),
+        )
+    }
+
     pub(crate) fn init_render(&mut self) {
         if !self.renderer_initialized {
             if let Some(renderer) = self.renderer.as_mut() {
@@ -90,17 +285,52 @@ pub(crate) trait WPRendererImpl {
 
     fn render(&mut self, width: u32, height: u32);
 
+    /// Renders what the last `render` call did again, without advancing the animation
+    fn render_last_frame(&mut self, width: u32, height: u32) {
+        self.render(width, height);
+    }
+
     fn clear_color(&self) -> (f32, f32, f32) {
         (0.0, 0.0, 0.0)
     }
+
+    /// Called when the output playback is paused or resumed, the renderer won't be asked
+    /// to render while paused so it only needs to stop any background work
+    fn set_paused(&mut self, _paused: bool) {}
+
+    /// Called along with `set_paused` when the output only has to keep showing its current frame
+    /// for a while, the renderer can release what it only needs to animate
+    fn set_static(&mut self, _static_frame: bool) {}
+
+    /// Whether a frame was rendered since the wallpaper was set up, a paused output without one
+    /// would stay blank
+    fn has_frame(&self) -> bool {
+        true
+    }
+
+    /// When the renderer will have a new frame to show, `None` if it always has one
+    fn next_frame_time(&self) -> Option<Instant> {
+        None
+    }
+
+    /// How the wallpaper should be laid out when its aspect ratio doesn't match the output one
+    fn set_scaling(&mut self, _mode: ScalingMode) {}
+
+    /// Called when the user changes a property of the wallpaper, the value has already been
+    /// validated against the property kind. Unknown properties are ignored.
+    fn set_property(&mut self, _key: &str, _value: &PropertyValue) {}
 }
 
 pub(crate) trait VideoRenderingBackend: WPRendererImpl {
-    fn setup_video_wallpaper(&mut self, video_path: PathBuf);
+    fn setup_video_wallpaper(
+        &mut self,
+        video_path: PathBuf,
+        config: VideoConfig,
+    ) -> anyhow::Result<()>;
 }
 
 pub(crate) trait SceneRenderingBackend: WPRendererImpl {
-    fn setup_scene_wallpaper(&mut self, scene_package: &ScenePackage);
+    fn setup_scene_wallpaper(&mut self, scene_package: &ScenePackage) -> anyhow::Result<()>;
 }
 
 enum RenderingBackend {
@@ -1,8 +1,12 @@
 use crate::egl::EGLState;
+use crate::event_broadcaster::EventBroadcaster;
+use crate::power::PowerSaving;
+use crate::transition::Transition;
 use crate::wallpaper::Wallpaper;
 use crate::wallpaper_renderer::WPRenderer;
 use fps_counter::FPSCounter;
 use gl::COLOR_BUFFER_BIT;
+use image::RgbaImage;
 use khronos_egl::ATTRIB_NONE;
 use smithay_client_toolkit::compositor::Surface;
 use smithay_client_toolkit::output::OutputInfo;
@@ -29,10 +33,20 @@ use smithay_client_toolkit::{
 };
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::os::fd::AsRawFd;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use crossbeam::channel::{RecvError, Sender};
 use wayland_egl::WlEglSurface;
-use waypaper_engine_shared::ipc::{IPCResponse, InternalRequest};
+use waypaper_engine_shared::config::VideoConfig;
+use waypaper_engine_shared::ipc::{
+    IPCEvent, IPCResponse, InternalRequest, OutputStatus, ScalingMode,
+};
+use waypaper_engine_shared::project::{PropertyValue, WEProject};
+
+// Upper bound on how long the main loop can wait for wayland events, so that IPC requests are
+// still handled when no frame callback is pending (e.g. when every output is paused)
+const EVENTS_POLL_TIMEOUT_MS: i32 = 10;
 
 pub struct RenderingContext {
     _connection: Rc<Connection>,
@@ -42,7 +56,10 @@ pub struct RenderingContext {
 }
 
 impl RenderingContext {
-    pub fn new(internal_ipc_tx: Sender<(InternalRequest, Sender<IPCResponse>)>) -> Self {
+    pub fn new(
+        internal_ipc_tx: Sender<(InternalRequest, Sender<IPCResponse>)>,
+        events: EventBroadcaster,
+    ) -> Self {
         let connection = Rc::new(Connection::connect_to_env().unwrap());
         let egl_state = Rc::new(EGLState::new(connection.clone()));
         let (globals, event_queue): (GlobalList, EventQueue<WLState>) =
@@ -55,6 +72,7 @@ impl RenderingContext {
             &globals,
             queue_handle,
             internal_ipc_tx,
+            events,
         );
 
         tracing::info!("Created WL state");
@@ -68,6 +86,8 @@ impl RenderingContext {
     }
 
     pub fn tick(&mut self) {
+        self.draw_scheduled_layers();
+
         let dispatched = self
             .event_queue
             .dispatch_pending(&mut self.wl_state)
@@ -82,7 +102,18 @@ impl RenderingContext {
             .expect("Failed to flush wayland event queue");
 
         if let Some(guard) = self.event_queue.prepare_read() {
-            guard.read().expect("Failed to read wayland events");
+            let mut fds = [libc::pollfd {
+                fd: guard.connection_fd().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            }];
+
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), 1, self.poll_timeout()) };
+
+            // Otherwise, dropping the guard cancels the read
+            if ready > 0 {
+                guard.read().expect("Failed to read wayland events");
+            }
         }
 
         self.event_queue
@@ -95,6 +126,32 @@ impl RenderingContext {
         }*/
     }
 
+    fn draw_scheduled_layers(&mut self) {
+        let now = Instant::now();
+        let queue_handle = &self.wl_state.queue_handle;
+
+        for layer in self.wl_state.layers.values_mut() {
+            if layer.scheduled_draw.is_some_and(|time| time <= now) {
+                layer.draw(queue_handle);
+            }
+        }
+    }
+
+    fn poll_timeout(&self) -> i32 {
+        let now = Instant::now();
+
+        self.wl_state
+            .layers
+            .values()
+            .filter_map(|layer| layer.scheduled_draw)
+            .map(|time| {
+                // Round up, waking up early would only make us spin until the deadline
+                let micros = time.saturating_duration_since(now).as_micros();
+                i32::try_from(micros.div_ceil(1000)).unwrap_or(i32::MAX)
+            })
+            .fold(EVENTS_POLL_TIMEOUT_MS, i32::min)
+    }
+
     pub fn get_outputs(&mut self) -> OutputsList {
         self.event_queue.roundtrip(&mut self.wl_state).unwrap();
 
@@ -112,16 +169,164 @@ impl RenderingContext {
         )
     }
 
-    pub(crate) fn set_wallpaper(&mut self, output: (&WlOutput, &OutputInfo), wallpaper: Wallpaper) {
+    pub(crate) fn set_wallpaper(
+        &mut self,
+        output: (&WlOutput, &OutputInfo),
+        wallpaper: Wallpaper,
+    ) -> anyhow::Result<()> {
         let output_name = output.1.name.clone().unwrap();
 
-        let layer = if self.wl_state.layers.contains_key(&output_name.clone()) {
-            self.wl_state.layers.get_mut(&output_name.clone()).unwrap()
-        } else {
+        let new_layer = !self.wl_state.layers.contains_key(&output_name);
+        let layer = if new_layer {
             self.wl_state.setup_layer(output)
+        } else {
+            self.wl_state.layers.get_mut(&output_name).unwrap()
         };
 
-        layer.set_wallpaper(wallpaper);
+        if let Err(err) = layer.set_wallpaper(wallpaper, &self.wl_state.queue_handle) {
+            if new_layer {
+                // Don't leave an empty layer on screen
+                self.wl_state.layers.remove(&output_name);
+            }
+
+            return Err(err);
+        }
+
+        // A new wallpaper always starts playing
+        if layer.paused {
+            self.set_paused(&output_name, Some(false));
+        }
+
+        Ok(())
+    }
+
+    /// Pauses or resumes the given output, `None` toggles its current state.
+    /// Returns the new state, or `None` if no wallpaper is displayed on this output.
+    pub fn set_paused(&mut self, screen: &str, paused: Option<bool>) -> Option<bool> {
+        let queue_handle = self.event_queue.handle();
+        let layer = self.wl_state.layers.get_mut(screen)?;
+        let paused = paused.unwrap_or(!layer.paused);
+
+        if layer.paused != paused {
+            layer.set_paused(paused, &queue_handle);
+
+            let screen = screen.to_owned();
+            self.wl_state.events.emit(if paused {
+                IPCEvent::PlaybackPaused { screen }
+            } else {
+                IPCEvent::PlaybackResumed { screen }
+            });
+        }
+
+        Some(paused)
+    }
+
+    /// Project of the wallpaper displayed on the given output, if any
+    pub fn wallpaper_project(&self, screen: &str) -> Option<&WEProject> {
+        self.wl_state
+            .layers
+            .get(screen)?
+            .wallpaper
+            .as_ref()
+            .map(Wallpaper::project)
+    }
+
+    /// Applies an already validated property value to the wallpaper displayed on the given output.
+    /// Returns `false` if no wallpaper is displayed on this output.
+    pub fn set_property(&mut self, screen: &str, key: &str, value: PropertyValue) -> bool {
+        match self.wl_state.layers.get_mut(screen) {
+            Some(layer) => layer.set_property(key, value),
+            None => false,
+        }
+    }
+
+    /// Returns `false` if no wallpaper is displayed on the given output
+    pub fn set_scaling(&mut self, screen: &str, mode: ScalingMode) -> bool {
+        match self.wl_state.layers.get_mut(screen) {
+            Some(layer) => {
+                layer.renderer.set_scaling(mode);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Limits the framerate of the given output, or of every output if `screen` is `None`.
+    /// Returns `false` if no wallpaper is displayed on the given output.
+    pub fn set_fps_cap(&mut self, screen: Option<&str>, fps_cap: Option<u32>) -> bool {
+        match screen {
+            Some(screen) => match self.wl_state.layers.get_mut(screen) {
+                Some(layer) => {
+                    layer.fps_cap = fps_cap;
+                    true
+                }
+                None => false,
+            },
+            None => {
+                self.wl_state.global_fps_cap = fps_cap;
+                true
+            }
+        }
+    }
+
+    /// Applies power saving restrictions to every output, `None` lifts them
+    pub fn set_power_saving(&mut self, power_saving: Option<PowerSaving>) {
+        self.wl_state.power_saving = power_saving;
+
+        let queue_handle = &self.wl_state.queue_handle;
+        for layer in self.wl_state.layers.values_mut() {
+            layer.set_power_saving(power_saving, queue_handle);
+        }
+    }
+
+    /// Sets how every output switches wallpapers, `None` switches instantly
+    pub fn set_transition(&mut self, transition: Option<Transition>) {
+        self.wl_state.transition = transition;
+
+        for layer in self.wl_state.layers.values_mut() {
+            layer.renderer.set_transition(transition);
+        }
+    }
+
+    /// Sets the decoding settings of the next video wallpapers
+    pub fn set_video_config(&mut self, config: VideoConfig) {
+        self.wl_state.video_config = config;
+
+        for layer in self.wl_state.layers.values_mut() {
+            layer.renderer.set_video_config(config);
+        }
+    }
+
+    /// Returns `None` if no wallpaper is displayed on the given output, or if it has no frame to
+    /// show yet (still loading or in the middle of a transition)
+    pub fn screenshot(&mut self, screen: &str) -> Option<RgbaImage> {
+        self.wl_state
+            .layers
+            .get_mut(screen)
+            .filter(|layer| layer.renderer.has_frame())
+            .map(SimpleLayer::screenshot)
+    }
+
+    pub fn get_status(&self) -> Vec<OutputStatus> {
+        self.wl_state
+            .layers
+            .iter()
+            .map(|(name, layer)| OutputStatus {
+                screen: name.clone(),
+                wallpaper_id: layer
+                    .wallpaper
+                    .as_ref()
+                    .and_then(|wp| wp.project().workshop_id),
+                wallpaper_type: layer.wallpaper.as_ref().map(Wallpaper::wp_type),
+                fps: layer.fps,
+                size: (layer.width, layer.height),
+                paused: layer.paused,
+                scaling: layer.renderer.scaling(),
+                fps_cap: layer.effective_fps_cap(self.wl_state.global_fps_cap),
+                // Playlists are handled by the app state
+                playlist: None,
+            })
+            .collect()
     }
 }
 
@@ -136,7 +341,13 @@ pub struct WLState {
     layer_shell: LayerShell,
 
     pub layers: HashMap<String, SimpleLayer>,
+    /// Applies to every output, on top of their own limit
+    global_fps_cap: Option<u32>,
+    power_saving: Option<PowerSaving>,
+    transition: Option<Transition>,
+    video_config: VideoConfig,
     new_output_tx: Sender<(InternalRequest, Sender<IPCResponse>)>,
+    events: EventBroadcaster,
 }
 
 impl WLState {
@@ -146,6 +357,7 @@ impl WLState {
         globals: &GlobalList,
         queue_handle: QueueHandle<Self>,
         new_output_tx: Sender<(InternalRequest, Sender<IPCResponse>)>,
+        events: EventBroadcaster,
     ) -> Self {
         Self {
             connection,
@@ -160,7 +372,12 @@ impl WLState {
             queue_handle,
 
             layers: HashMap::new(),
+            global_fps_cap: None,
+            power_saving: None,
+            transition: None,
+            video_config: VideoConfig::default(),
             new_output_tx,
+            events,
         }
     }
 
@@ -204,6 +421,10 @@ impl WLState {
         layer.commit();
         self.connection.roundtrip().unwrap(); // Block until the wayland server has processed everything
 
+        let mut renderer = WPRenderer::new(self.connection.clone(), self.egl_state.clone());
+        renderer.set_transition(self.transition);
+        renderer.set_video_config(self.video_config);
+
         let layer = SimpleLayer {
             exit: false,
             first_configure: true,
@@ -218,8 +439,15 @@ impl WLState {
             egl_window_surface,
             output: (output.0.clone(), output.1.clone()),
 
-            renderer: WPRenderer::new(self.connection.clone(), self.egl_state.clone()),
+            renderer,
             fps_counter: FPSCounter::new(),
+            fps: 0,
+            paused: false,
+            frame_callback_pending: false,
+            fps_cap: None,
+            power_saving: self.power_saving,
+            last_draw: Instant::now(),
+            scheduled_draw: None,
             wallpaper: None,
         };
 
@@ -253,6 +481,42 @@ impl OutputsList {
     }
 }
 
+/// Identifies the monitor as `make model serial`, so that it can be recognized whatever output it
+/// is plugged into. `None` if the compositor doesn't tell all three.
+fn monitor_identity(info: &OutputInfo) -> Option<String> {
+    identify_monitor(
+        &info.make,
+        &info.model,
+        info.description.as_deref(),
+        info.name.as_deref(),
+    )
+}
+
+fn identify_monitor(
+    make: &str,
+    model: &str,
+    description: Option<&str>,
+    name: Option<&str>,
+) -> Option<String> {
+    let known = |value: &str| !value.is_empty() && !value.eq_ignore_ascii_case("unknown");
+    if !known(make) || !known(model) {
+        return None;
+    }
+
+    // wl_output has no serial, but wlroots based compositors describe outputs as
+    // "make model serial (name)"
+    let details = description?.strip_prefix(&format!("{} {}", make, model))?;
+    let details = match name {
+        Some(name) => details
+            .strip_suffix(&format!("({})", name))
+            .unwrap_or(details),
+        None => details,
+    };
+
+    let serial = details.trim();
+    known(serial).then(|| format!("{} {} {}", make, model, serial))
+}
+
 impl Deref for OutputsList {
     type Target = HashMap<WlOutput, OutputInfo>;
 
@@ -284,6 +548,14 @@ pub struct SimpleLayer {
     renderer: WPRenderer,
     wallpaper: Option<Wallpaper>,
     fps_counter: FPSCounter,
+    fps: usize,
+    paused: bool,
+    frame_callback_pending: bool,
+    fps_cap: Option<u32>,
+    power_saving: Option<PowerSaving>,
+    last_draw: Instant,
+    /// Set when a frame callback came too early, the main loop draws the layer at that time
+    scheduled_draw: Option<Instant>,
 }
 
 impl Drop for SimpleLayer {
@@ -326,7 +598,18 @@ impl CompositorHandler for WLState {
             .values_mut()
             .find(|layer| layer.layer.wl_surface() == surface)
         {
-            layer.draw(qh);
+            layer.frame_callback_pending = false;
+
+            // While halted, the compositor keeps showing the last buffer we committed
+            if layer.halted() {
+                return;
+            }
+
+            // Don't render the same frame again, or faster than allowed
+            match layer.next_draw_time(self.global_fps_cap) {
+                Some(time) if time > Instant::now() => layer.scheduled_draw = Some(time),
+                _ => layer.draw(qh),
+            }
         }
     }
 
@@ -357,14 +640,15 @@ impl OutputHandler for WLState {
     fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
         match self.output_state.info(&output) {
             Some(infos) => {
+                let monitor = monitor_identity(&infos);
+                let screen = infos.name.unwrap();
+                self.events.emit(IPCEvent::OutputAdded {
+                    screen: screen.clone(),
+                });
+
                 let (resp_tx, _resp_rx) = crossbeam::channel::unbounded::<IPCResponse>();
                 self.new_output_tx
-                    .send((
-                        InternalRequest::NewOutput {
-                            screen: infos.name.unwrap(),
-                        },
-                        resp_tx,
-                    ))
+                    .send((InternalRequest::NewOutput { screen, monitor }, resp_tx))
                     .unwrap_or_else(|e| tracing::error!("Failed to send new output event: {}", e));
             }
             None => tracing::error!("Could not retrieve new output info"),
@@ -376,6 +660,10 @@ impl OutputHandler for WLState {
     }
 
     fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        if let Some(screen) = self.output_state.info(&output).and_then(|infos| infos.name) {
+            self.events.emit(IPCEvent::OutputRemoved { screen });
+        }
+
         if let Some(layer) = self.layers.values().find(|layer| layer.output.0 == output) {
             self.layers
                 .remove(&layer.output.1.name.clone().unwrap())
@@ -477,16 +765,157 @@ impl SeatHandler for WLState {
 }
 
 impl SimpleLayer {
-    pub fn set_wallpaper(&mut self, wp: Wallpaper) {
-        self.renderer.setup_wallpaper(&wp);
+    pub fn set_wallpaper(
+        &mut self,
+        wp: Wallpaper,
+        qh: &QueueHandle<WLState>,
+    ) -> anyhow::Result<()> {
+        let was_halted = self.halted();
+
+        // Setting up may drop the renderer of the previous wallpaper along with its GL resources
+        self.egl_state.attach_context(self.egl_window_surface);
+        let result = self.renderer.setup_wallpaper(&wp);
+        if result.is_ok() {
+            self.renderer.init_render();
+        }
+        self.egl_state.detach_context();
+
+        result?;
         self.wallpaper = Some(wp);
 
+        // Saving power doesn't stop the new wallpaper from showing its first frame
+        self.apply_halted(was_halted, qh);
+
+        Ok(())
+    }
+
+    pub fn set_property(&mut self, key: &str, value: PropertyValue) -> bool {
+        let Some(property) = self
+            .wallpaper
+            .as_mut()
+            .and_then(|wp| wp.project_mut().general.as_mut())
+            .and_then(|general| general.properties.get_mut(key))
+        else {
+            return false;
+        };
+
+        self.renderer.set_property(key, &value);
+        property.value = value;
+
+        true
+    }
+
+    pub fn set_paused(&mut self, paused: bool, qh: &QueueHandle<WLState>) {
+        let was_halted = self.halted();
+        self.paused = paused;
+        self.apply_halted(was_halted, qh);
+    }
+
+    pub fn set_power_saving(
+        &mut self,
+        power_saving: Option<PowerSaving>,
+        qh: &QueueHandle<WLState>,
+    ) {
+        let was_halted = self.halted();
+        self.power_saving = power_saving;
+        self.apply_halted(was_halted, qh);
+    }
+
+    /// Whether the animation is stopped, by the user or to save power. Saving power only stops
+    /// the animation once there is a frame to keep on screen.
+    fn halted(&self) -> bool {
+        let power_halted = matches!(
+            self.power_saving,
+            Some(PowerSaving::Pause | PowerSaving::Static)
+        );
+
+        self.paused || (power_halted && self.renderer.has_frame())
+    }
+
+    /// Propagates a change of `halted()` to the renderer and restarts drawing when needed
+    fn apply_halted(&mut self, was_halted: bool, qh: &QueueHandle<WLState>) {
+        let halted = self.halted();
+        self.renderer
+            .set_static(halted && self.power_saving == Some(PowerSaving::Static));
+
+        if halted == was_halted {
+            return;
+        }
+
+        self.renderer.set_paused(halted);
+
+        if halted {
+            self.fps = 0;
+            self.scheduled_draw = None;
+        } else if !self.frame_callback_pending && !self.first_configure {
+            // Restart the frame callbacks loop
+            self.draw(qh);
+        }
+    }
+
+    fn effective_fps_cap(&self, global_fps_cap: Option<u32>) -> Option<u32> {
+        let power_fps_cap = match self.power_saving {
+            Some(PowerSaving::FpsCap(fps)) => Some(fps),
+            _ => None,
+        };
+
+        [self.fps_cap, global_fps_cap, power_fps_cap]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Earliest time at which drawing again would show something new within the FPS cap
+    fn next_draw_time(&self, global_fps_cap: Option<u32>) -> Option<Instant> {
+        let capped = self
+            .effective_fps_cap(global_fps_cap)
+            .map(|fps| self.last_draw + Duration::from_secs_f64(1.0 / f64::from(fps)));
+
+        capped.max(self.renderer.next_frame_time())
+    }
+
+    /// Renders the displayed frame again and reads it back, without presenting anything
+    /// The renderer must have a frame, or only the clear color would be captured
+    pub fn screenshot(&mut self) -> RgbaImage {
+        let mut pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+
         self.egl_state.attach_context(self.egl_window_surface);
-        self.renderer.init_render();
+
+        unsafe {
+            let clear_color = self.renderer.clear_color();
+
+            gl::ClearColor(clear_color.0, clear_color.1, clear_color.2, 1.0);
+            gl::Clear(COLOR_BUFFER_BIT);
+
+            self.renderer.render_last_frame(self.width, self.height);
+
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                i32::try_from(self.width).unwrap(),
+                i32::try_from(self.height).unwrap(),
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+
         self.egl_state.detach_context();
+
+        let mut image = RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("Screenshot buffer doesn't match the layer size");
+        // OpenGL rows start from the bottom of the image
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        image
     }
 
     pub fn draw(&mut self, qh: &QueueHandle<WLState>) {
+        let was_halted = self.halted();
+        self.scheduled_draw = None;
+        self.last_draw = Instant::now();
+
         let width = self.width;
         let height = self.height;
 
@@ -520,20 +949,27 @@ impl SimpleLayer {
         // Now that buffers are swapped, we can reset the egl context
         self.egl_state.detach_context();
 
-        // Request our next frame
-        self.layer
-            .wl_surface()
-            .frame(qh, self.layer.wl_surface().clone());
+        // Request our next frame, unless halted
+        if !self.halted() {
+            self.layer
+                .wl_surface()
+                .frame(qh, self.layer.wl_surface().clone());
+            self.frame_callback_pending = true;
+        }
 
         // Commit to present.
         self.layer.commit();
 
-        let fps = self.fps_counter.tick();
+        self.fps = self.fps_counter.tick();
         tracing::debug!(
             "Output {} : {} FPS",
             self.output.1.name.as_ref().unwrap(),
-            fps
+            self.fps
         );
+        // The first frame of a wallpaper set while saving power was just shown
+        if self.halted() != was_halted {
+            self.apply_halted(was_halted, qh);
+        }
     }
 }
 
@@ -549,3 +985,54 @@ impl ProvidesRegistryState for WLState {
     }
     registry_handlers![OutputState, SeatState];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor_identities() {
+        let identify = |description, name| identify_monitor("Dell", "U2720Q", description, name);
+
+        assert_eq!(
+            identify(Some("Dell U2720Q 8ZJ4T53 (DP-1)"), Some("DP-1")).as_deref(),
+            Some("Dell U2720Q 8ZJ4T53")
+        );
+        // Compositors which don't add the output name
+        assert_eq!(
+            identify(Some("Dell U2720Q 8ZJ4T53"), Some("DP-1")).as_deref(),
+            Some("Dell U2720Q 8ZJ4T53")
+        );
+        assert_eq!(
+            identify(Some("Dell U2720Q 8ZJ4T53"), None).as_deref(),
+            Some("Dell U2720Q 8ZJ4T53")
+        );
+
+        // No serial to tell identical monitors apart
+        assert_eq!(identify(Some("Dell U2720Q (DP-1)"), Some("DP-1")), None);
+        assert_eq!(
+            identify(Some("Dell U2720Q Unknown (DP-1)"), Some("DP-1")),
+            None
+        );
+        assert_eq!(identify(None, Some("DP-1")), None);
+        // A description which isn't made of the make and model
+        assert_eq!(identify(Some("Built-in display"), Some("eDP-1")), None);
+    }
+
+    #[test]
+    fn unknown_monitors() {
+        let description = Some("Unknown U2720Q 8ZJ4T53 (DP-1)");
+        assert_eq!(
+            identify_monitor("Unknown", "U2720Q", description, Some("DP-1")),
+            None
+        );
+        assert_eq!(
+            identify_monitor("unknown", "U2720Q", description, Some("DP-1")),
+            None
+        );
+        assert_eq!(
+            identify_monitor("Dell", "", Some("Dell  8ZJ4T53"), Some("DP-1")),
+            None
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Software decompression of the S3TC (DXT1, DXT3 and DXT5) block compressed textures
+//!
+//! Pixels are compressed by blocks of 4x4, the blocks on the right and bottom edges being
+//! partially used when the size of the texture isn't a multiple of 4.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlockFormat {
+    /// Colors only, with an optional 1-bit alpha
+    Dxt1,
+    /// Colors and explicit 4-bit alpha
+    Dxt3,
+    /// Colors and interpolated alpha
+    Dxt5,
+}
+
+impl BlockFormat {
+    fn block_size(self) -> usize {
+        match self {
+            BlockFormat::Dxt1 => 8,
+            BlockFormat::Dxt3 | BlockFormat::Dxt5 => 16,
+        }
+    }
+}
+
+/// Size in bytes of a compressed texture
+pub(crate) fn compressed_size(width: u32, height: u32, format: BlockFormat) -> usize {
+    width.div_ceil(4) as usize * height.div_ceil(4) as usize * format.block_size()
+}
+
+/// Decompresses a texture into RGBA pixels
+///
+/// `blocks` must hold [`compressed_size`] bytes.
+pub(crate) fn decompress(blocks: &[u8], width: u32, height: u32, format: BlockFormat) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_per_row = width.div_ceil(4);
+    let mut rgba = vec![0; width * height * 4];
+
+    for (i, block) in blocks.chunks_exact(format.block_size()).enumerate() {
+        let pixels = match format {
+            BlockFormat::Dxt1 => decode_color_block(block, true),
+            BlockFormat::Dxt3 => {
+                let mut pixels = decode_color_block(&block[8..], false);
+                let alphas = u64::from_le_bytes(block[..8].try_into().unwrap());
+                for (j, pixel) in pixels.iter_mut().enumerate() {
+                    // 4-bit values, scaled up to 8 bits
+                    pixel[3] = ((alphas >> (4 * j)) & 0xf) as u8 * 17;
+                }
+                pixels
+            }
+            BlockFormat::Dxt5 => {
+                let mut pixels = decode_color_block(&block[8..], false);
+                for (pixel, alpha) in pixels.iter_mut().zip(decode_alpha_block(&block[..8])) {
+                    pixel[3] = alpha;
+                }
+                pixels
+            }
+        };
+
+        let (block_x, block_y) = (i % blocks_per_row * 4, i / blocks_per_row * 4);
+        for (j, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (block_x + j % 4, block_y + j / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    rgba
+}
+
+/// Decodes the 16 pixels of an 8 bytes color block, in rows
+///
+/// DXT1 blocks whose first color is not greater than the second one have a single interpolated
+/// color and transparent pixels, DXT3 and DXT5 blocks always have two interpolated colors.
+fn decode_color_block(block: &[u8], dxt1: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let c0 = rgb565(color0);
+    let c1 = rgb565(color1);
+    let mix = |weight0: u16, weight1: u16| {
+        let total = weight0 + weight1;
+        let channel = |a: u8, b: u8| {
+            ((u16::from(a) * weight0 + u16::from(b) * weight1 + total / 2) / total) as u8
+        };
+        [
+            channel(c0[0], c1[0]),
+            channel(c0[1], c1[1]),
+            channel(c0[2], c1[2]),
+            255,
+        ]
+    };
+
+    let palette = if !dxt1 || color0 > color1 {
+        [c0, c1, mix(2, 1), mix(1, 2)]
+    } else {
+        [c0, c1, mix(1, 1), [0, 0, 0, 0]]
+    };
+
+    std::array::from_fn(|i| palette[(indices >> (2 * i)) as usize & 0b11])
+}
+
+/// Decodes the 16 alpha values of a DXT5 alpha block, in rows
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let (alpha0, alpha1) = (u16::from(block[0]), u16::from(block[1]));
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    let mut palette = [0; 8];
+    palette[0] = alpha0 as u8;
+    palette[1] = alpha1 as u8;
+    if alpha0 > alpha1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = (((7 - i) * alpha0 + i * alpha1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = (((5 - i) * alpha0 + i * alpha1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    std::array::from_fn(|i| palette[(indices >> (3 * i)) as usize & 0b111])
+}
+
+/// Expands a 16 bits RGB565 color to 8 bits per channel
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = (color >> 11) as u8 & 0x1f;
+    let g = (color >> 5) as u8 & 0x3f;
+    let b = color as u8 & 0x1f;
+
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+        255,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+    /// Decompresses a single 4x4 block
+    fn block_pixels(block: &[u8], format: BlockFormat) -> Vec<[u8; 4]> {
+        assert_eq!(block.len(), compressed_size(4, 4, format));
+        decompress(block, 4, 4, format)
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn dxt1_four_colors() {
+        // Red then blue, the first pixels use the indices 0 to 3, the others 0
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0x00, 0x00, 0x00];
+        let pixels = block_pixels(&block, BlockFormat::Dxt1);
+
+        assert_eq!(
+            pixels[..4],
+            [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255]]
+        );
+        assert!(pixels[4..].iter().all(|&pixel| pixel == RED));
+    }
+
+    #[test]
+    fn dxt1_transparent_index() {
+        // Blue then red, which isn't greater: a single interpolated color and transparency
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xff, 0xff, 0xff];
+        let pixels = block_pixels(&block, BlockFormat::Dxt1);
+
+        assert_eq!(pixels[..4], [BLUE, RED, [128, 0, 128, 255], TRANSPARENT]);
+        assert!(pixels[4..].iter().all(|&pixel| pixel == TRANSPARENT));
+    }
+
+    #[test]
+    fn dxt3_explicit_alpha() {
+        // Pixel i has the alpha i, scaled up to 8 bits. The colors never have a transparent
+        // index, even with blue not greater than red.
+        let block = [
+            0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe, 0x1f, 0x00, 0x00, 0xf8, 0xff, 0xff,
+            0xff, 0xff,
+        ];
+        let pixels = block_pixels(&block, BlockFormat::Dxt3);
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            assert_eq!(*pixel, [170, 0, 85, i as u8 * 17], "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn dxt5_eight_alphas() {
+        // Alphas 255 then 0, pixels use the indices 0 to 7 twice, on white
+        let block = [
+            0xff, 0x00, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let alphas: Vec<u8> = block_pixels(&block, BlockFormat::Dxt5)
+            .iter()
+            .map(|pixel| pixel[3])
+            .collect();
+
+        let palette = [255, 0, 218, 182, 145, 109, 72, 36];
+        assert_eq!(alphas, [palette, palette].concat());
+    }
+
+    #[test]
+    fn dxt5_six_alphas() {
+        // Alphas 0 then 255, with fully transparent and opaque indices
+        let block = [
+            0x00, 0xff, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let pixels = block_pixels(&block, BlockFormat::Dxt5);
+
+        let palette = [0, 255, 51, 102, 153, 204, 0, 255];
+        for (i, pixel) in pixels.iter().enumerate() {
+            assert_eq!(*pixel, [255, 255, 255, palette[i % 8]], "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn partial_edge_blocks() {
+        // A red block then a blue one, of which a single column and 3 rows are used
+        let blocks = [
+            0x00, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(compressed_size(5, 3, BlockFormat::Dxt1), blocks.len());
+
+        let rgba = decompress(&blocks, 5, 3, BlockFormat::Dxt1);
+        assert_eq!(rgba.len(), 5 * 3 * 4);
+        for row in rgba.chunks_exact(5 * 4) {
+            assert_eq!(row, [RED, RED, RED, RED, BLUE].concat());
+        }
+    }
+}
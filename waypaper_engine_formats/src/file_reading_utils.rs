@@ -0,0 +1,53 @@
+use std::io::{self, BufRead, Cursor, Read};
+
+pub fn read_u32(cursor: &mut Cursor<Vec<u8>>) -> io::Result<u32> {
+    let mut first_4_bytes: [u8; 4] = [0; 4];
+    cursor.read_exact(&mut first_4_bytes)?;
+
+    Ok(u32::from_le_bytes(first_4_bytes))
+}
+
+pub fn read_i32(cursor: &mut Cursor<Vec<u8>>) -> io::Result<i32> {
+    let mut first_4_bytes: [u8; 4] = [0; 4];
+    cursor.read_exact(&mut first_4_bytes)?;
+
+    Ok(i32::from_le_bytes(first_4_bytes))
+}
+
+pub fn read_f32(cursor: &mut Cursor<Vec<u8>>) -> io::Result<f32> {
+    let mut first_4_bytes: [u8; 4] = [0; 4];
+    cursor.read_exact(&mut first_4_bytes)?;
+
+    Ok(f32::from_le_bytes(first_4_bytes))
+}
+
+pub fn read_null_terminated_str(cursor: &mut Cursor<Vec<u8>>) -> io::Result<String> {
+    let mut bytes = vec![];
+
+    cursor.read_until(0x00, &mut bytes)?;
+    bytes.pop(); // Remove the null terminator
+
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn read_str(data: &mut Cursor<Vec<u8>>) -> io::Result<String> {
+    let size = read_u32(data)?;
+    read_sized_str(data, size)
+}
+
+fn read_sized_str(data: &mut Cursor<Vec<u8>>, size: u32) -> io::Result<String> {
+    let mut bytes = vec![];
+    data.take(u64::from(size)).read_to_end(&mut bytes)?;
+
+    if bytes.len() != size as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn read_color(data: &mut Cursor<Vec<u8>>) -> io::Result<(u8, u8, u8, u8)> {
+    let number = read_u32(data)?;
+
+    Ok(number.to_le_bytes().into())
+}
@@ -0,0 +1,6 @@
+//! Readers of the Wallpaper Engine file formats, shared by the daemon and the CLI
+
+mod dxt;
+pub mod file_reading_utils;
+pub mod scene_package;
+pub mod tex_file;
@@ -0,0 +1,236 @@
+use crate::file_reading_utils::{read_str, read_u32};
+use anyhow::{Context, anyhow, bail};
+use std::collections::HashMap;
+use std::fs::create_dir_all;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+use tracing::Level;
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub name: String,
+    /// Offset of the file data, from the end of the package header
+    pub offset: u32,
+    /// Size of the file, in bytes
+    pub size: u32,
+}
+
+impl FileEntry {
+    /// The size in a human-readable unit, e.g. "12.3 kB"
+    pub fn formatted_size(&self) -> String {
+        let size = f64::from(self.size);
+
+        if self.size >= 1_000_000 {
+            format!("{:.1} MB", size / 1_000_000.0)
+        } else if self.size >= 1000 {
+            format!("{:.1} kB", size / 1000.0)
+        } else {
+            format!("{} B", self.size)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileContent {
+    pub name: String,
+    data: Vec<u8>,
+}
+
+impl FileContent {
+    pub fn as_str(&self) -> String {
+        String::from_utf8_lossy(&self.data).to_string()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn save_to_disk(&self, path: &PathBuf) -> io::Result<()> {
+        fs::write(path, self.bytes())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenePackage {
+    /// The files of the package, in the order they are stored
+    pub entries: Vec<FileEntry>,
+    pub contents: HashMap<String, FileContent>,
+}
+
+impl ScenePackage {
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        tracing::debug!("Unpacking Scene Package !");
+
+        if !path.is_file() {
+            bail!("{} is not a file", path.to_string_lossy());
+        }
+
+        let mut data: Cursor<Vec<u8>> = Cursor::new(fs::read(path)?);
+        tracing::debug!("Data Length: {}", data.get_ref().len());
+
+        let file_count = read_header(&mut data)?;
+
+        let files = read_files(&mut data, file_count).context("Unable to read PKG file list")?;
+
+        let mut contents: HashMap<String, FileContent> = HashMap::new();
+
+        let header_offset = data.position();
+        for entry in &files {
+            if tracing::enabled!(Level::DEBUG) {
+                tracing::debug!(
+                    "\tName: {} - Offset: {}, Size: {}",
+                    entry.name,
+                    entry.offset,
+                    entry.formatted_size()
+                );
+            }
+            contents.insert(
+                entry.name.clone(),
+                read_file(&mut data, header_offset, entry)?,
+            );
+        }
+
+        Ok(Self {
+            entries: files,
+            contents,
+        })
+    }
+
+    /// Writes every file of the package into `dir`, keeping their relative paths
+    pub fn save_to_disk(&self, dir: &Path) -> anyhow::Result<()> {
+        // Packages come from the workshop, don't let them write anywhere else. Every name is
+        // checked first so that a rejected package leaves nothing behind.
+        if let Some(c) = self.contents.values().find(|c| {
+            !Path::new(&c.name)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        }) {
+            bail!("{} is not a relative path inside the package", c.name);
+        }
+
+        if !dir.try_exists()? {
+            create_dir_all(dir)?;
+        }
+
+        if !dir.is_dir() {
+            bail!("{} is not a directory", dir.to_string_lossy());
+        }
+
+        for c in self.contents.values() {
+            let path = &dir.join(c.name.clone());
+
+            if let Some(parent_dir) = path.parent() {
+                create_dir_all(parent_dir)?;
+            }
+
+            c.save_to_disk(path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_file(&self, name: &str) -> Option<&FileContent> {
+        self.contents.get(name)
+    }
+}
+
+fn read_header(data: &mut Cursor<Vec<u8>>) -> anyhow::Result<u32> {
+    let version = read_str(data).context("Unable to read PKG file header")?;
+    if !version.starts_with("PKGV") {
+        bail!("Error reading PKG file header: {}", version);
+    }
+
+    if version != "PKGV0001" {
+        tracing::warn!(
+            "Trying to unpack unsupported PKG file version: {}, if you encounter bugs please report them on the git repository",
+            version
+        );
+    }
+
+    let file_count = read_u32(data).context("Unable to read PKG file count")?;
+    tracing::debug!("{version} - File count : {file_count}");
+
+    Ok(file_count)
+}
+
+fn read_files(data: &mut Cursor<Vec<u8>>, file_count: u32) -> io::Result<Vec<FileEntry>> {
+    let mut files = vec![];
+
+    for _ in 0..file_count {
+        files.push(FileEntry {
+            name: read_str(data)?,
+            offset: read_u32(data)?,
+            size: read_u32(data)?,
+        });
+    }
+
+    Ok(files)
+}
+
+fn read_file(
+    data: &mut Cursor<Vec<u8>>,
+    header_offset: u64,
+    file: &FileEntry,
+) -> anyhow::Result<FileContent> {
+    data.rewind()?;
+    data.set_position(header_offset + file.offset as u64);
+
+    let mut content = vec![];
+    data.take(u64::from(file.size)).read_to_end(&mut content)?;
+
+    if content.len() != file.size as usize {
+        return Err(anyhow!(
+            "{} is truncated ({} bytes out of {})",
+            file.name,
+            content.len(),
+            file.size
+        ));
+    }
+
+    Ok(FileContent {
+        name: file.name.clone(),
+        data: content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(names: &[&str]) -> ScenePackage {
+        let contents = names.iter().map(|name| {
+            let content = FileContent {
+                name: (*name).to_owned(),
+                data: b"{}".to_vec(),
+            };
+            ((*name).to_owned(), content)
+        });
+
+        ScenePackage {
+            entries: vec![],
+            contents: contents.collect(),
+        }
+    }
+
+    #[test]
+    fn unsafe_names_write_nothing() {
+        let dir = std::env::temp_dir().join(format!("waypaper_pkg_test_{}", std::process::id()));
+
+        for name in [
+            "../escape.json",
+            "/tmp/absolute.json",
+            "materials/../../a.json",
+        ] {
+            let package = package(&["scene.json", "materials/b.json", name]);
+            assert!(package.save_to_disk(&dir).is_err(), "{name} was accepted");
+            assert!(!dir.exists(), "{name} left files behind");
+        }
+
+        package(&["scene.json", "materials/b.json"])
+            .save_to_disk(&dir)
+            .unwrap();
+        assert!(dir.join("materials/b.json").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,621 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::{Context, anyhow, bail};
+use bitflags::bitflags;
+use cgmath::{InnerSpace, Vector2};
+use image::{ImageFormat, RgbaImage, imageops};
+use lz4_flex::decompress;
+use num_enum_derive::TryFromPrimitive;
+
+use crate::dxt::{self, BlockFormat};
+use crate::file_reading_utils::{
+    read_color, read_f32, read_i32, read_null_terminated_str, read_u32,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum TextureFormat {
+    RGBA8888 = 0,
+    DXT5 = 4,
+    DXT3 = 6,
+    DXT1 = 7,
+    RG88 = 8,
+    R8 = 9,
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TextureFlags: u32 {
+        const NoInterpolation = 1;
+        const ClampUVs = 1 << 1;
+        const IsSpritesheet = 1 << 2;
+    }
+}
+
+pub struct Container {
+    pub version: ContainerVersion,
+    pub image_count: u32,
+    pub freeimage_format: Option<FreeImageFormat>,
+}
+
+// This enum comes from FreeImage as Wallpaper Engine relies on it to provide us the image format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum FreeImageFormat {
+    Bmp = 0,
+    Ico = 1,
+    Jpeg = 2,
+    Jng = 3,
+    Koala = 4,
+    LbmOrIff = 5,
+    Mng = 6,
+    Pbm = 7,
+    PbmRaw = 8,
+    Pcd = 9,
+    Pcx = 10,
+    Pgm = 11,
+    PgmRaw = 12,
+    Png = 13,
+    Ppm = 14,
+    PpmRaw = 15,
+    Ras = 16,
+    Targa = 17,
+    Tiff = 18,
+    Wbmp = 19,
+    Psd = 20,
+    Cut = 21,
+    Xbm = 22,
+    Xpm = 23,
+    Dds = 24,
+    Gif = 25,
+    Hdr = 26,
+    // This format is disabled in FreeImage itself for security reasons, so it shouldn't be used in wallpaper engine textures anyway
+    // FAXG3 = 27,
+    Sgi = 28,
+    Exr = 29,
+    J2K = 30,
+    Jp2 = 31,
+    Pfm = 32,
+    Pict = 33,
+    Raw = 34,
+    WebP = 35,
+    Jxr = 36,
+}
+
+impl TryInto<ImageFormat> for FreeImageFormat {
+    type Error = ();
+
+    fn try_into(self) -> Result<ImageFormat, Self::Error> {
+        match self {
+            FreeImageFormat::Bmp => Ok(ImageFormat::Bmp),
+            FreeImageFormat::Ico => Ok(ImageFormat::Ico),
+            FreeImageFormat::Jpeg => Ok(ImageFormat::Jpeg),
+            FreeImageFormat::Jng => Err(()),
+            FreeImageFormat::Koala => Err(()),
+            FreeImageFormat::LbmOrIff => Err(()),
+            FreeImageFormat::Mng => Err(()),
+            FreeImageFormat::Pbm => Ok(ImageFormat::Pnm),
+            FreeImageFormat::PbmRaw => Ok(ImageFormat::Pnm),
+            FreeImageFormat::Pcd => Err(()),
+            FreeImageFormat::Pcx => Err(()),
+            FreeImageFormat::Pgm => Ok(ImageFormat::Pnm),
+            FreeImageFormat::PgmRaw => Ok(ImageFormat::Pnm),
+            FreeImageFormat::Png => Ok(ImageFormat::Png),
+            FreeImageFormat::Ppm => Ok(ImageFormat::Pnm),
+            FreeImageFormat::PpmRaw => Ok(ImageFormat::Pnm),
+            FreeImageFormat::Ras => Err(()),
+            FreeImageFormat::Targa => Ok(ImageFormat::Tga),
+            FreeImageFormat::Tiff => Ok(ImageFormat::Tiff),
+            FreeImageFormat::Wbmp => Err(()),
+            FreeImageFormat::Psd => Err(()),
+            FreeImageFormat::Cut => Err(()),
+            FreeImageFormat::Xbm => Err(()),
+            FreeImageFormat::Xpm => Err(()),
+            FreeImageFormat::Dds => Ok(ImageFormat::Dds),
+            FreeImageFormat::Gif => Ok(ImageFormat::Gif),
+            FreeImageFormat::Hdr => Ok(ImageFormat::Hdr),
+            FreeImageFormat::Sgi => Err(()),
+            FreeImageFormat::Exr => Ok(ImageFormat::OpenExr),
+            FreeImageFormat::J2K => Err(()),
+            FreeImageFormat::Jp2 => Err(()),
+            FreeImageFormat::Pfm => Err(()),
+            FreeImageFormat::Pict => Err(()),
+            FreeImageFormat::Raw => Err(()),
+            FreeImageFormat::WebP => Ok(ImageFormat::WebP),
+            FreeImageFormat::Jxr => Err(()),
+        }
+    }
+}
+
+pub struct MipmapEntry {
+    pub width: u32,
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerVersion {
+    TEXB001,
+    TEXB002,
+    TEXB003,
+}
+
+impl TryFrom<&str> for ContainerVersion {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "TEXB0001" => Self::TEXB001,
+            "TEXB0002" => Self::TEXB002,
+            "TEXB0003" => Self::TEXB003,
+            _ => return Err(()),
+        })
+    }
+}
+
+pub struct Header {
+    pub format: TextureFormat,
+    pub texture_flags: TextureFlags,
+    pub texture_width: u32,
+    pub texture_height: u32,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub dominant_color: (u8, u8, u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameInfoContainerVersion {
+    TEXS0001,
+    TEXS0002,
+    TEXS0003,
+}
+
+impl TryFrom<&str> for FrameInfoContainerVersion {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "TEXS0001" => Self::TEXS0001,
+            "TEXS0002" => Self::TEXS0002,
+            "TEXS0003" => Self::TEXS0003,
+            _ => return Err(()),
+        })
+    }
+}
+
+pub struct FrameInfoContainer {
+    pub version: FrameInfoContainerVersion,
+    pub frame_infos: Vec<FrameInfo>,
+    pub sprite_size: Option<Vector2<u32>>,
+}
+
+pub struct FrameInfo {
+    pub image_id: i32,
+    pub frame_time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub x_axis: Vector2<f32>,
+    pub y_axis: Vector2<f32>,
+}
+
+pub struct TexFile {
+    pub header: Header,
+    pub container: Container,
+    /// Mipmaps of each image, largest first
+    pub images: Vec<Vec<MipmapEntry>>,
+    pub frames_infos: Option<FrameInfoContainer>,
+}
+
+impl TexFile {
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        tracing::debug!("Unpacking Tex File !");
+        let data: Vec<u8> = fs::read(path)?;
+        Self::from_bytes(data)
+    }
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let mut data = Cursor::new(bytes);
+        let data_length = data.get_ref().len();
+        tracing::debug!("Data Length : {data_length}");
+
+        let header = read_header(&mut data).context("Unable to read texture header")?;
+        let container = read_container(&mut data).context("Unable to read texture container")?;
+
+        let images = read_images(&mut data, &container).context("Unable to read texture images")?;
+
+        let frames_infos = if header.texture_flags.contains(TextureFlags::IsSpritesheet) {
+            tracing::debug!("Reading Frames Infos:");
+            Some(read_frame_info(&mut data).context("Unable to read spritesheet frames")?)
+        } else {
+            None
+        };
+
+        if data.position() as usize != data_length {
+            bail!(
+                "Malformed Tex File: {} trailing bytes",
+                data_length - data.position() as usize
+            );
+        }
+
+        Ok(Self {
+            header,
+            container,
+            images,
+            frames_infos,
+        })
+    }
+
+    /// Decodes a mipmap of an image (0 being the full size one)
+    ///
+    /// DXT textures are decompressed in software, so that they can be used without S3TC support.
+    /// Raw textures are padded to a larger texture size, they are cropped to the size of the image.
+    pub fn to_rgba(&self, image: usize, mip: usize) -> anyhow::Result<RgbaImage> {
+        let mipmap = self
+            .images
+            .get(image)
+            .and_then(|mipmaps| mipmaps.get(mip))
+            .ok_or_else(|| anyhow!("The texture has no mipmap {mip} for image {image}"))?;
+
+        if let Some(format) = self.container.freeimage_format {
+            let image_format: ImageFormat = format
+                .try_into()
+                .map_err(|_| anyhow!("Unsupported image format {format:?}"))?;
+
+            let image = image::load_from_memory_with_format(&mipmap.bytes, image_format)
+                .with_context(|| format!("Unable to decode the {format:?} image"))?;
+            return Ok(image.to_rgba8());
+        }
+
+        let texture = decode_raw(mipmap, self.header.format)?;
+
+        // Mipmaps past the 32nd would shift the whole size away, and empty mipmaps have nothing
+        // to keep
+        let mip_size = |size: u32, mipmap_size: u32| {
+            size.checked_shr(mip as u32)
+                .unwrap_or(0)
+                .max(1)
+                .min(mipmap_size)
+        };
+        let width = mip_size(self.header.image_width, mipmap.width);
+        let height = mip_size(self.header.image_height, mipmap.height);
+        if (width, height) == texture.dimensions() {
+            return Ok(texture);
+        }
+
+        Ok(imageops::crop_imm(&texture, 0, 0, width, height).to_image())
+    }
+
+    /// Decodes a frame of a spritesheet
+    ///
+    /// Frames are cut along their bounding box, rotated frames are left as they are.
+    pub fn frame_to_rgba(&self, frame: &FrameInfo) -> anyhow::Result<RgbaImage> {
+        let image = usize::try_from(frame.image_id)
+            .map_err(|_| anyhow!("Invalid frame image {}", frame.image_id))?;
+        let image = self.to_rgba(image, 0)?;
+
+        let x = (frame.x.max(0.0) as u32).min(image.width());
+        let y = (frame.y.max(0.0) as u32).min(image.height());
+        let width = (frame.width.round() as u32).min(image.width() - x);
+        let height = (frame.height.round() as u32).min(image.height() - y);
+        if width == 0 || height == 0 {
+            bail!(
+                "Frame at {}x{} is outside of its {}x{} image",
+                frame.x,
+                frame.y,
+                image.width(),
+                image.height()
+            );
+        }
+
+        Ok(imageops::crop_imm(&image, x, y, width, height).to_image())
+    }
+}
+
+fn decode_raw(mipmap: &MipmapEntry, format: TextureFormat) -> anyhow::Result<RgbaImage> {
+    let pixels = mipmap.width as usize * mipmap.height as usize;
+
+    let rgba = match format {
+        TextureFormat::RGBA8888 => mipmap_bytes(mipmap, format, pixels * 4)?.to_vec(),
+        // Single channel textures are greyscale
+        TextureFormat::R8 => mipmap_bytes(mipmap, format, pixels)?
+            .iter()
+            .flat_map(|&r| [r, r, r, 255])
+            .collect(),
+        // Greyscale with alpha
+        TextureFormat::RG88 => mipmap_bytes(mipmap, format, pixels * 2)?
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        TextureFormat::DXT1 => decompress_blocks(mipmap, format, BlockFormat::Dxt1)?,
+        TextureFormat::DXT3 => decompress_blocks(mipmap, format, BlockFormat::Dxt3)?,
+        TextureFormat::DXT5 => decompress_blocks(mipmap, format, BlockFormat::Dxt5)?,
+    };
+
+    Ok(RgbaImage::from_raw(mipmap.width, mipmap.height, rgba)
+        .expect("The pixels were checked to fill the mipmap"))
+}
+
+fn decompress_blocks(
+    mipmap: &MipmapEntry,
+    format: TextureFormat,
+    block_format: BlockFormat,
+) -> anyhow::Result<Vec<u8>> {
+    let size = dxt::compressed_size(mipmap.width, mipmap.height, block_format);
+    let blocks = mipmap_bytes(mipmap, format, size)?;
+
+    Ok(dxt::decompress(
+        blocks,
+        mipmap.width,
+        mipmap.height,
+        block_format,
+    ))
+}
+
+fn mipmap_bytes(mipmap: &MipmapEntry, format: TextureFormat, size: usize) -> anyhow::Result<&[u8]> {
+    mipmap.bytes.get(..size).ok_or_else(|| {
+        anyhow!(
+            "{} bytes are not enough for a {}x{} {format:?} mipmap",
+            mipmap.bytes.len(),
+            mipmap.width,
+            mipmap.height
+        )
+    })
+}
+
+fn read_header(data: &mut Cursor<Vec<u8>>) -> anyhow::Result<Header> {
+    let version = read_null_terminated_str(data)?;
+    if version != "TEXV0005" {
+        bail!("Unsupported texture version {version}");
+    }
+    let version2 = read_null_terminated_str(data)?;
+    if version2 != "TEXI0001" {
+        bail!("Unsupported texture info version {version2}");
+    }
+
+    tracing::debug!("{version} - {version2}");
+
+    let format = read_u32(data)?;
+    let format =
+        TextureFormat::try_from(format).map_err(|_| anyhow!("Unknown texture format {format}"))?;
+    let flags = read_u32(data)?;
+    let flags = TextureFlags::from_bits(flags)
+        .ok_or_else(|| anyhow!("Unknown texture flags {flags:#x}"))?;
+    let texture_width = read_u32(data)?;
+    let texture_height = read_u32(data)?;
+    let image_width = read_u32(data)?;
+    let image_height = read_u32(data)?;
+    let dominant_color = read_color(data)?;
+
+    tracing::debug!("Texture info:");
+    tracing::debug!("\tFormat: {format:?}");
+    tracing::debug!("\tFlags: {flags:?}");
+    tracing::debug!("\tTexture Size: {texture_width}x{texture_height}");
+    tracing::debug!("\tImage Size: {image_width}x{image_height}");
+    tracing::debug!("\tDominant Color: {dominant_color:?}");
+
+    Ok(Header {
+        format,
+        texture_flags: flags,
+        texture_width,
+        texture_height,
+        image_width,
+        image_height,
+        dominant_color,
+    })
+}
+
+fn read_container(data: &mut Cursor<Vec<u8>>) -> anyhow::Result<Container> {
+    let version = read_null_terminated_str(data)?;
+    let version = ContainerVersion::try_from(version.as_str())
+        .map_err(|_| anyhow!("Unsupported texture container version {version}"))?;
+    tracing::debug!("Container version: {version:?}");
+
+    let image_count = read_u32(data)?;
+    let freeimage_format = match version {
+        ContainerVersion::TEXB001 | ContainerVersion::TEXB002 => None,
+        ContainerVersion::TEXB003 => {
+            let format = read_i32(data)?;
+            if format > 0 {
+                Some(
+                    FreeImageFormat::try_from(format as u32)
+                        .map_err(|_| anyhow!("Unknown FreeImage format {format}"))?,
+                )
+            } else {
+                None
+            }
+        }
+    };
+
+    tracing::debug!("\tImage Count: {image_count}");
+    match freeimage_format {
+        None => tracing::debug!("\tImage Format: No format"),
+        Some(ref format) => tracing::debug!("\tImage Format: {format:?}"),
+    }
+
+    Ok(Container {
+        version,
+        image_count,
+        freeimage_format,
+    })
+}
+
+fn read_mipmap(
+    cursor: &mut Cursor<Vec<u8>>,
+    container_version: &ContainerVersion,
+) -> anyhow::Result<MipmapEntry> {
+    let width = read_u32(cursor)?;
+    let height = read_u32(cursor)?;
+
+    let (is_compressed, image_size_uncompressed) = match container_version {
+        ContainerVersion::TEXB001 => (false, None),
+        ContainerVersion::TEXB002 | ContainerVersion::TEXB003 => {
+            let compression_flag = read_u32(cursor)?;
+            if compression_flag != 0 && compression_flag != 1 {
+                bail!("Invalid mipmap compression flag {compression_flag}");
+            }
+            let is_compressed = compression_flag != 0;
+
+            let image_size_uncompressed = read_u32(cursor)?;
+
+            (is_compressed, Some(image_size_uncompressed))
+        }
+    };
+
+    let image_size = read_u32(cursor)?;
+
+    let mut bytes = vec![];
+
+    if is_compressed {
+        let mut raw_bytes = vec![];
+
+        cursor
+            .take(u64::from(image_size))
+            .read_to_end(&mut raw_bytes)?;
+
+        let uncompressed_size = image_size_uncompressed.unwrap() as usize;
+
+        bytes = decompress(&raw_bytes, uncompressed_size)
+            .map_err(|err| anyhow!("Failed texture decompression: {err}"))?;
+        if bytes.len() != uncompressed_size {
+            bail!(
+                "Failed texture decompression: got {} bytes instead of {}",
+                bytes.len(),
+                uncompressed_size
+            );
+        }
+    } else {
+        cursor.take(u64::from(image_size)).read_to_end(&mut bytes)?;
+    }
+
+    if !is_compressed && bytes.len() != image_size as usize {
+        bail!(
+            "Mipmap is truncated ({} bytes out of {})",
+            bytes.len(),
+            image_size
+        );
+    }
+
+    tracing::debug!("\t\tWidth: {width}");
+    tracing::debug!("\t\tHeight: {height}");
+    tracing::debug!("\t\tIs Compressed: {is_compressed}");
+
+    if is_compressed {
+        tracing::debug!(
+            "\t\tImage Size Uncompressed: {}",
+            image_size_uncompressed.unwrap()
+        );
+    }
+
+    tracing::debug!("\t\tImage Size: {image_size}",);
+
+    Ok(MipmapEntry {
+        width,
+        height,
+        bytes,
+    })
+}
+
+fn read_images(
+    data: &mut Cursor<Vec<u8>>,
+    container: &Container,
+) -> anyhow::Result<Vec<Vec<MipmapEntry>>> {
+    let mut images = vec![];
+
+    for i in 0..container.image_count {
+        tracing::debug!("Reading Image {i}: ");
+
+        let mipmap_count = read_u32(data)?;
+        tracing::debug!("\tMipmap Count: {mipmap_count}");
+
+        let mut mipmap_entries = vec![];
+
+        for j in 0..mipmap_count {
+            tracing::debug!("\tReading Mipmap {j} :");
+            mipmap_entries.push(
+                read_mipmap(data, &container.version)
+                    .with_context(|| format!("Unable to read mipmap {j} of image {i}"))?,
+            );
+        }
+
+        images.push(mipmap_entries);
+    }
+
+    Ok(images)
+}
+
+fn read_frame_info(data: &mut Cursor<Vec<u8>>) -> anyhow::Result<FrameInfoContainer> {
+    let version = read_null_terminated_str(data)?;
+    let version = FrameInfoContainerVersion::try_from(version.as_str())
+        .map_err(|_| anyhow!("Unsupported frame info container version {version}"))?;
+
+    tracing::debug!("\tFrame Info Container version: {version:?}");
+
+    let frame_count = read_i32(data)?;
+    tracing::debug!("\tFrame Count: {frame_count}");
+
+    let sprite_size = match version {
+        FrameInfoContainerVersion::TEXS0001 | FrameInfoContainerVersion::TEXS0002 => None,
+        FrameInfoContainerVersion::TEXS0003 => Some(Vector2::new(read_u32(data)?, read_u32(data)?)),
+    };
+
+    tracing::debug!("\tSprite Size: {sprite_size:?}");
+
+    let mut frames = vec![];
+
+    for i in 0..frame_count {
+        tracing::debug!("\tReading frame {i} infos:");
+
+        let image_id = read_i32(data)?;
+
+        let (frame_time, x, y, x_axis, y_axis) = match version {
+            FrameInfoContainerVersion::TEXS0001 => (
+                read_f32(data)?,
+                read_i32(data)? as f32,
+                read_i32(data)? as f32,
+                Vector2::new(read_i32(data)? as f32, read_i32(data)? as f32),
+                Vector2::new(read_i32(data)? as f32, read_i32(data)? as f32),
+            ),
+            FrameInfoContainerVersion::TEXS0002 | FrameInfoContainerVersion::TEXS0003 => (
+                read_f32(data)?,
+                read_f32(data)?,
+                read_f32(data)?,
+                Vector2::new(read_f32(data)?, read_f32(data)?),
+                Vector2::new(read_f32(data)?, read_f32(data)?),
+            ),
+        };
+
+        let width = x_axis.magnitude();
+        let height = y_axis.magnitude();
+
+        let frame = FrameInfo {
+            image_id,
+            frame_time,
+            x,
+            y,
+            width: x_axis.magnitude(),
+            height: y_axis.magnitude(),
+            x_axis: x_axis / width,
+            y_axis: y_axis / height,
+        };
+
+        tracing::debug!("\t\tImage ID: {}", frame.image_id);
+        tracing::debug!("\t\tFrame Time: {}", frame.frame_time);
+        tracing::debug!("\t\tX: {}", frame.x);
+        tracing::debug!("\t\tY: {}", frame.y);
+        tracing::debug!("\t\tX Axis: {:?}", frame.x_axis);
+        tracing::debug!("\t\tY Axis: {:?}", frame.y_axis);
+        tracing::debug!("\t\tWidth: {}", frame.width);
+        tracing::debug!("\t\tHeight: {}", frame.height);
+
+        frames.push(frame);
+    }
+
+    Ok(FrameInfoContainer {
+        version,
+        frame_infos: frames,
+        sprite_size,
+    })
+}
@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::de::{DeTable, DeValue};
+
+use crate::ipc::ScalingMode;
+use crate::serde_utils::{as_frame_buffer_size, as_percentage};
+
+pub const DEFAULT_POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+/// Frames a video keeps queued before showing them, so that frames decoded out of order can be
+/// shown in order
+pub const MIN_FRAME_BUFFER_SIZE: usize = 16;
+
+/// Settings of the daemon, read from `$XDG_CONFIG_HOME/waypaper_engine/config.toml`. Every key
+/// is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Verbosity of the daemon logs, overridden by `RUST_LOG`
+    pub log_level: LogLevel,
+    /// Path of the IPC socket, see `get_socket_path` for the default one
+    pub socket: Option<PathBuf>,
+    /// Wallpaper Engine workshop directories, found from the Steam libraries if empty
+    pub workshop_dirs: Vec<PathBuf>,
+    /// Scaling mode of the outputs without one set from the CLI
+    pub scaling: ScalingMode,
+    /// FPS cap of every output
+    pub fps_cap: Option<NonZeroU32>,
+    pub video: VideoConfig,
+    pub power: PowerConfig,
+    pub transition: TransitionConfig,
+    /// Settings of specific outputs, by output name
+    pub outputs: BTreeMap<String, OutputConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    /// Decoded frames queued ahead of the displayed one, at least [`MIN_FRAME_BUFFER_SIZE`]
+    #[serde(deserialize_with = "as_frame_buffer_size")]
+    pub frame_buffer_size: NonZeroUsize,
+    /// Frame buffers allocated upfront by each video decoder
+    pub frame_pool_size: NonZeroUsize,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        VideoConfig {
+            frame_buffer_size: NonZeroUsize::new(20).unwrap(),
+            frame_pool_size: NonZeroUsize::new(25).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// What to do with animated wallpapers when running on battery
+    pub on_battery: BatteryAction,
+    /// The action only applies once the battery level is at or below this percentage
+    #[serde(deserialize_with = "as_percentage")]
+    pub battery_threshold: u8,
+    /// FPS cap used by `BatteryAction::LowerFps`
+    pub battery_fps: NonZeroU32,
+    /// Directory listing the power supplies
+    pub power_supply_root: PathBuf,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig {
+            on_battery: BatteryAction::default(),
+            battery_threshold: 100,
+            battery_fps: NonZeroU32::new(10).unwrap(),
+            power_supply_root: PathBuf::from(DEFAULT_POWER_SUPPLY_ROOT),
+        }
+    }
+}
+
+/// What to do with animated wallpapers when running on battery
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatteryAction {
+    /// Keep playing as usual
+    #[default]
+    None,
+    /// Pause the animation, keeping the current frame displayed
+    Pause,
+    /// Cap the framerate of every output
+    LowerFps,
+    /// Stop drawing and decoding entirely, keeping the current frame displayed
+    Static,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransitionConfig {
+    pub kind: TransitionKind,
+    /// Duration of the transition in milliseconds, 0 switches instantly
+    pub duration_ms: u64,
+}
+
+impl Default for TransitionConfig {
+    fn default() -> Self {
+        TransitionConfig {
+            kind: TransitionKind::default(),
+            duration_ms: 500,
+        }
+    }
+}
+
+/// How the previous wallpaper makes way for the new one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionKind {
+    /// Switch instantly
+    None,
+    /// Fade the new wallpaper in over the old one
+    #[default]
+    Crossfade,
+    /// Push the old wallpaper out to the left
+    Slide,
+    /// Reveal the new wallpaper from left to right
+    Wipe,
+    /// Zoom into the old wallpaper while fading the new one in
+    Zoom,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Overrides the global scaling mode
+    pub scaling: Option<ScalingMode>,
+    /// FPS cap of this output, on top of the global one
+    pub fps_cap: Option<NonZeroU32>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        /// Line and column of the error, starting at 1
+        position: Option<(usize, usize)>,
+        /// Full name of the key at fault, such as `outputs.DP-1.fps_cap`
+        key: Option<String>,
+        message: String,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "unable to read {}: {}", path.to_string_lossy(), error)
+            }
+            ConfigError::Invalid {
+                path,
+                position,
+                key,
+                message,
+            } => {
+                write!(f, "{}", path.to_string_lossy())?;
+                if let Some((line, column)) = position {
+                    write!(f, ":{}:{}", line, column)?;
+                }
+                if let Some(key) = key {
+                    write!(f, ": {}", key)?;
+                }
+                write!(f, ": {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration file at `path`, or at the default path if `None`. A missing default
+    /// file gives the default configuration.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        match path {
+            Some(path) => Config::read(path),
+            None => {
+                let path = config_path();
+                if path.exists() {
+                    Config::read(&path)
+                } else {
+                    Ok(Config::default())
+                }
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Config, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        toml::from_str(&content).map_err(|error| invalid_config(path, &content, &error))
+    }
+
+    /// The configured socket path, or the default one
+    pub fn socket_path(&self) -> PathBuf {
+        self.socket.clone().unwrap_or_else(crate::get_socket_path)
+    }
+
+    /// Scaling mode of the given output, unless set from the CLI
+    pub fn output_scaling(&self, screen: &str) -> ScalingMode {
+        self.outputs
+            .get(screen)
+            .and_then(|output| output.scaling)
+            .unwrap_or(self.scaling)
+    }
+
+    /// FPS cap of the given output, unless set from the CLI
+    pub fn output_fps_cap(&self, screen: &str) -> Option<u32> {
+        self.outputs
+            .get(screen)
+            .and_then(|output| output.fps_cap)
+            .map(NonZeroU32::get)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/waypaper_engine/config.toml`
+pub fn config_path() -> PathBuf {
+    let config_dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(config) => PathBuf::from(config),
+        Err(_) => PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"),
+    };
+
+    config_dir.join("waypaper_engine").join("config.toml")
+}
+
+/// Locates the error in the file and names the key at fault
+///
+/// The key is found from the spans of the parsed document, so that inline tables, dotted keys
+/// and quoted names are resolved like the deserializer sees them. Syntax errors leave no document
+/// to walk, the key is then guessed from the line of the error and the last `[table]` header
+/// above it, which misses the inline tables and the keys split over several lines. Errors in
+/// arrays name the array itself, such as `outputs` for a `[[outputs]]` table.
+fn invalid_config(path: &Path, content: &str, error: &toml::de::Error) -> ConfigError {
+    let mut position = None;
+    let mut key = None;
+
+    if let Some(before) = error.span().and_then(|span| content.get(..span.start)) {
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        position = Some((
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        ));
+
+        key = match DeTable::parse(content) {
+            Ok(document) => key_at(document.get_ref(), before.len()),
+            Err(_) => key_from_line(content, line_start),
+        };
+    }
+
+    ConfigError::Invalid {
+        path: path.to_path_buf(),
+        position,
+        key,
+        message: error.message().to_owned(),
+    }
+}
+
+/// Dotted name of the innermost key whose name or value contains the byte `offset`
+///
+/// The span of a table defined by a `[table]` header or by dotted keys doesn't cover its keys,
+/// so every table is searched rather than only the one around the offset.
+fn key_at(table: &DeTable, offset: usize) -> Option<String> {
+    table.iter().find_map(|(name, value)| {
+        if name.span().contains(&offset) {
+            return Some(name.get_ref().to_string());
+        }
+
+        match value.get_ref() {
+            DeValue::Table(inner) => key_at(inner, offset),
+            _ => None,
+        }
+        .map(|inner| format!("{}.{}", name.get_ref(), inner))
+        .or_else(|| {
+            value
+                .span()
+                .contains(&offset)
+                .then(|| name.get_ref().to_string())
+        })
+    })
+}
+
+/// Guesses the key of a line from its text and the last `[table]` header above it
+fn key_from_line(content: &str, line_start: usize) -> Option<String> {
+    let line = content[line_start..].lines().next().unwrap_or_default();
+    let table = content[..line_start]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| line.starts_with('['))
+        .and_then(|header| header.trim_start_matches('[').split(']').next())
+        .map(|table| table.trim().replace('"', ""));
+
+    line.split_once('=')
+        .map(|(name, _)| name.trim().replace('"', ""))
+        .filter(|name| !name.is_empty() && !name.starts_with('['))
+        .map(|name| match table {
+            Some(table) => format!("{}.{}", table, name),
+            None => name,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line, column and key reported for an invalid configuration
+    fn locate(content: &str) -> (Option<(usize, usize)>, Option<String>) {
+        let error = toml::from_str::<Config>(content).unwrap_err();
+        match invalid_config(Path::new("config.toml"), content, &error) {
+            ConfigError::Invalid { position, key, .. } => (position, key),
+            ConfigError::Io { .. } => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn top_level_keys() {
+        assert_eq!(
+            locate("log_level = \"info\"\nfps_cap = 0\n"),
+            (Some((2, 11)), Some("fps_cap".to_owned()))
+        );
+        assert_eq!(
+            locate("log_level = \"info\"\nbogus = 1\n"),
+            (Some((2, 1)), Some("bogus".to_owned()))
+        );
+    }
+
+    #[test]
+    fn table_keys() {
+        assert_eq!(
+            locate("scaling = \"fill\"\n\n[power]\non_battery = \"explode\"\n"),
+            (Some((4, 14)), Some("power.on_battery".to_owned()))
+        );
+        assert_eq!(
+            locate("[transition]\nkind = \"crossfade\"\nduration_ms = \"x\"\n"),
+            (Some((3, 15)), Some("transition.duration_ms".to_owned()))
+        );
+    }
+
+    #[test]
+    fn quoted_table_keys() {
+        assert_eq!(
+            locate(
+                "[outputs.\"DP-1\"]\nscaling = \"fit\"\n\n[outputs.\"HDMI-A-1\"]\nfps_cap = 0\n"
+            ),
+            (Some((5, 11)), Some("outputs.HDMI-A-1.fps_cap".to_owned()))
+        );
+    }
+
+    #[test]
+    fn inline_tables() {
+        assert_eq!(
+            locate("outputs = { \"DP-1\" = { fps_cap = 0 } }\n"),
+            (Some((1, 34)), Some("outputs.DP-1.fps_cap".to_owned()))
+        );
+    }
+
+    #[test]
+    fn small_frame_buffers() {
+        let (position, key) = locate("[video]\nframe_buffer_size = 10\n");
+        assert_eq!(position, Some((2, 21)));
+        assert_eq!(key.as_deref(), Some("video.frame_buffer_size"));
+
+        let config: Config = toml::from_str("[video]\nframe_buffer_size = 16\n").unwrap();
+        assert_eq!(config.video.frame_buffer_size.get(), MIN_FRAME_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn dotted_keys() {
+        assert_eq!(
+            locate("power.on_battery = \"x\"\n"),
+            (Some((1, 20)), Some("power.on_battery".to_owned()))
+        );
+    }
+
+    #[test]
+    fn arrays_name_the_array() {
+        assert_eq!(
+            locate("[[outputs]]\nscaling = \"fit\"\n"),
+            (Some((1, 1)), Some("outputs".to_owned()))
+        );
+    }
+
+    #[test]
+    fn syntax_errors_guess_the_key_from_the_line() {
+        let (position, key) = locate("[video]\nframe_buffer_size = \n");
+        assert_eq!(position.map(|(line, _)| line), Some(2));
+        assert_eq!(key.as_deref(), Some("video.frame_buffer_size"));
+    }
+}
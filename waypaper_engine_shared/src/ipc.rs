@@ -1,31 +1,329 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
 use subenum::subenum;
 
+use crate::project::WallpaperType;
+
 #[subenum(IPCRequest)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum InternalRequest {
+    /// `screen` is either an output name, `*` for every output or a glob pattern such as `DP-*`
     #[subenum(IPCRequest)]
     SetWallpaper { id: u64, screen: String },
+    /// Same as `SetWallpaper`, for any directory containing a `project.json`
+    #[subenum(IPCRequest)]
+    SetWallpaperPath { path: PathBuf, screen: String },
     #[subenum(IPCRequest)]
     KillDaemon,
     #[subenum(IPCRequest)]
     ListOutputs,
-    
-    NewOutput { screen: String },
+    #[subenum(IPCRequest)]
+    GetStatus,
+    #[subenum(IPCRequest)]
+    Subscribe,
+    #[subenum(IPCRequest)]
+    Pause { screen: String },
+    #[subenum(IPCRequest)]
+    Resume { screen: String },
+    #[subenum(IPCRequest)]
+    TogglePause { screen: String },
+    #[subenum(IPCRequest)]
+    SetScaling { screen: String, mode: ScalingMode },
+    /// Limits the framerate of `screen`, or of every output when `None`. `fps: None` removes the limit
+    #[subenum(IPCRequest)]
+    SetFpsCap { screen: Option<String>, fps: Option<u32> },
+    /// Changes a property of the wallpaper displayed on `screen`, `value` is parsed according
+    /// to the property kind
+    #[subenum(IPCRequest)]
+    SetProperty { screen: String, key: String, value: String },
+    /// Writes the frame currently displayed on `screen` as a PNG file at `path`
+    #[subenum(IPCRequest)]
+    Screenshot { screen: String, path: PathBuf },
+    /// Creates a playlist, or replaces the one with the same name
+    #[subenum(IPCRequest)]
+    SavePlaylist { playlist: Playlist },
+    #[subenum(IPCRequest)]
+    DeletePlaylist { name: String },
+    #[subenum(IPCRequest)]
+    ListPlaylists,
+    /// `screen` accepts the same patterns as `SetWallpaper`
+    #[subenum(IPCRequest)]
+    StartPlaylist { name: String, screen: String },
+    #[subenum(IPCRequest)]
+    StopPlaylist { screen: String },
+    /// Skips to the next wallpaper of the playlist running on `screen`
+    #[subenum(IPCRequest)]
+    NextWallpaper { screen: String },
+    #[subenum(IPCRequest)]
+    PreviousWallpaper { screen: String },
+    /// Lists the wallpaper switches planned by the schedule for the next 24 hours
+    #[subenum(IPCRequest)]
+    ListSchedule,
+    /// Reads the configuration file and `wallpapers.conf` again and applies their changes
+    #[subenum(IPCRequest)]
+    Reload,
+    /// Saves the wallpaper and settings of every output, replacing the profile with the same name
+    #[subenum(IPCRequest)]
+    SaveProfile { name: String },
+    /// Restores the wallpaper and settings of the outputs saved in the profile
+    #[subenum(IPCRequest)]
+    LoadProfile { name: String },
+    #[subenum(IPCRequest)]
+    DeleteProfile { name: String },
+    #[subenum(IPCRequest)]
+    ListProfiles,
+
+    /// `monitor` identifies the monitor plugged into the output, see `ProfileManager::register_output`
+    NewOutput { screen: String, monitor: Option<String> },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum IPCResponse {
     Success,
     Outputs(Vec<String>),
+    Status(Vec<OutputStatus>),
+    /// Outcome of a request applied to several outputs
+    OutputResults(Vec<OutputResult>),
+    Playlists(Vec<Playlist>),
+    Schedule(Vec<ScheduledSwitch>),
+    Profiles(Vec<Profile>),
+    /// Events are streamed as JSON lines to any client connecting to this socket
+    Subscribed { socket: String },
     Error(IPCError),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum IPCError {
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IPCEvent {
+    OutputAdded { screen: String },
+    OutputRemoved { screen: String },
+    WallpaperChanged {
+        screen: String,
+        wallpaper: WallpaperRef,
+    },
+    WallpaperLoadFailed {
+        screen: String,
+        wallpaper: WallpaperRef,
+        error: IPCError,
+    },
+    PlaybackPaused { screen: String },
+    PlaybackResumed { screen: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WallpaperRef {
+    /// A workshop item, looked up in the Wallpaper Engine workshop directory
+    Workshop(u64),
+    /// Any directory containing a `project.json`
+    Path(PathBuf),
+}
+
+impl Display for WallpaperRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WallpaperRef::Workshop(id) => write!(f, "{}", id),
+            WallpaperRef::Path(path) => write!(f, "{}", path.to_string_lossy()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputStatus {
+    pub screen: String,
+    pub wallpaper_id: Option<u64>,
+    pub wallpaper_type: Option<WallpaperType>,
+    pub fps: usize,
+    pub size: (u32, u32),
+    pub paused: bool,
+    pub scaling: ScalingMode,
+    /// The limit in effect on this output, taking the global one into account
+    pub fps_cap: Option<u32>,
+    /// Name of the playlist running on this output
+    pub playlist: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    /// Workshop ids of the wallpapers
+    pub wallpapers: Vec<u64>,
+    /// Seconds before switching to the next wallpaper
+    pub interval_secs: u64,
+    pub order: PlaylistOrder,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistOrder {
+    #[default]
+    Sequential,
+    /// Every wallpaper is shown once, in a random order, before the order is shuffled again
+    Shuffle,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Sorted by monitor
+    pub outputs: Vec<ProfileOutput>,
+}
+
+/// Settings of an output saved in a profile, `None` ones fall back to the configured defaults
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileOutput {
+    /// The monitor as `make model serial`, or the output name for monitors which can't be told
+    /// apart from others
+    pub screen: String,
+    pub wallpaper: Option<WallpaperRef>,
+    /// Property overrides of the wallpaper
+    pub properties: BTreeMap<String, String>,
+    pub scaling: Option<ScalingMode>,
+    pub fps_cap: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledSwitch {
+    /// Screen name or pattern the schedule rule applies to
+    pub screen: String,
+    pub wallpaper_id: u64,
+    /// The rule time as written in the schedule, e.g. "07:00" or "sunset+00:30"
+    pub rule: String,
+    /// Unix timestamp of the switch
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScalingMode {
+    /// Scale to cover the whole output, cropping what overflows
+    #[default]
+    Fill,
+    /// Scale to fit inside the output, with borders of the wallpaper clear color
+    Fit,
+    /// Ignore the aspect ratio and cover the whole output
+    Stretch,
+    /// Keep the original size, centered on the output
+    Center,
+    /// Repeat the wallpaper at its original size
+    Tile,
+}
+
+impl ScalingMode {
+    pub const ALL: [ScalingMode; 5] = [
+        ScalingMode::Fill,
+        ScalingMode::Fit,
+        ScalingMode::Stretch,
+        ScalingMode::Center,
+        ScalingMode::Tile,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalingMode::Fill => "fill",
+            ScalingMode::Fit => "fit",
+            ScalingMode::Stretch => "stretch",
+            ScalingMode::Center => "center",
+            ScalingMode::Tile => "tile",
+        }
+    }
+}
+
+impl Display for ScalingMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ScalingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScalingMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = ScalingMode::ALL.iter().map(|mode| mode.name()).collect();
+                format!(
+                    "unknown scaling mode {} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputResult {
+    pub screen: String,
+    /// `None` if the request succeeded on this output
+    pub error: Option<IPCError>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IPCError {
+    pub kind: IPCErrorKind,
+    /// Human-readable description of what went wrong
+    pub message: String,
+    /// The offending screen, wallpaper id or path
+    pub subject: Option<String>,
+    /// Underlying causes, from the outermost to the root cause
+    pub causes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IPCErrorKind {
     ScreenNotFound,
     WallpaperNotFound,
     UnsupportedWallpaperType,
     InternalError,
-    WallpaperLoadingError
+    WallpaperLoadingError,
+    InvalidArgument,
+    PropertyNotFound,
+    PlaylistNotFound,
+    InvalidConfig,
+    ProfileNotFound,
+}
+
+impl IPCError {
+    pub fn new(kind: IPCErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            subject: None,
+            causes: vec![],
+        }
+    }
+
+    pub fn with_subject(mut self, subject: impl Display) -> Self {
+        self.subject = Some(subject.to_string());
+        self
+    }
+
+    pub fn with_causes(mut self, causes: Vec<String>) -> Self {
+        self.causes = causes;
+        self
+    }
 }
+
+impl Display for IPCError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(subject) = &self.subject {
+            write!(f, " ({})", subject)?;
+        }
+
+        for cause in &self.causes {
+            write!(f, ": {}", cause)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for IPCError {}
@@ -1,21 +1,56 @@
-use std::env;
-use std::path::PathBuf;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
 
+use crate::steam::WorkshopDirError;
+
+pub mod config;
 pub mod ipc;
+pub mod library;
 pub mod project;
 pub mod serde_utils;
+pub mod steam;
+
+const DEFAULT_WAYLAND_DISPLAY: &str = "wayland-0";
+
+/// Wallpaper Engine workshop directories, from `overrides` (when not empty), the environment or
+/// the Steam libraries
+pub fn get_wpe_dirs(overrides: &[PathBuf]) -> Result<Vec<PathBuf>, WorkshopDirError> {
+    steam::find_workshop_dirs(overrides)
+}
+
+/// Default path of the daemon IPC socket, unique per user and per Wayland session:
+/// `$XDG_RUNTIME_DIR/waypaper-engine-$WAYLAND_DISPLAY.sock`
+pub fn get_socket_path() -> PathBuf {
+    let runtime_dir = runtime_dir();
 
-const WPE_DIR: &str = ".steam/steam/steamapps/workshop/content/431960/";
+    // WAYLAND_DISPLAY may also be an absolute path to the compositor socket
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or(DEFAULT_WAYLAND_DISPLAY.to_owned());
+    let display = Path::new(&display)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(DEFAULT_WAYLAND_DISPLAY.to_owned());
 
-pub fn get_wpe_dir() -> PathBuf {
-    let wpe_dir = PathBuf::from(env::var("HOME").expect("No HOME environment variable set ?"))
-        .join(WPE_DIR);
+    runtime_dir.join(format!("waypaper-engine-{display}.sock"))
+}
+
+/// `$XDG_RUNTIME_DIR`, or `/run/user/$UID` when it isn't set, as from ssh, cron or system units
+fn runtime_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
 
-    assert!(
-        wpe_dir.exists() && wpe_dir.is_dir(),
-        "Wallpaper Engine folder not found (tried path: {})",
-        wpe_dir.to_string_lossy()
-    );
+    // The owner of our own process entry is the current user
+    match fs::metadata("/proc/self") {
+        Ok(metadata) => PathBuf::from(format!("/run/user/{}", metadata.uid())),
+        Err(_) => env::temp_dir(),
+    }
+}
+
+pub fn get_events_socket_path(socket_path: &Path) -> PathBuf {
+    socket_path.with_extension("events.sock")
+}
 
-    wpe_dir
+pub fn get_lock_file_path(socket_path: &Path) -> PathBuf {
+    socket_path.with_extension("lock")
 }
@@ -0,0 +1,418 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::project::{WEProject, WallpaperType};
+
+// Bumped whenever `LibraryEntry` changes, older caches are then rebuilt from scratch
+const CACHE_VERSION: u32 = 2;
+
+/// Metadata of an installed wallpaper, as indexed by the `Library`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub id: u64,
+    pub title: String,
+    pub wallpaper_type: WallpaperType,
+    pub tags: Vec<String>,
+    pub content_rating: Option<String>,
+    /// Directory of the wallpaper, containing its project.json
+    pub path: PathBuf,
+    pub preview: Option<PathBuf>,
+    /// Total size of the wallpaper files, in bytes
+    pub size: u64,
+    /// Last modification of the wallpaper directory or its project.json, in seconds since the
+    /// unix epoch
+    pub modified: u64,
+}
+
+/// Index of the wallpapers found in the workshop directories.
+///
+/// The index is cached in `$XDG_CACHE_HOME/waypaper_engine/library.json`, so that only the
+/// wallpapers added or updated since the last scan have their project.json parsed again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Library {
+    version: u32,
+    roots: Vec<PathBuf>,
+    wallpapers: BTreeMap<u64, LibraryEntry>,
+    /// Where the index is cached, `None` without a cache directory
+    #[serde(skip)]
+    cache: Option<PathBuf>,
+}
+
+impl Library {
+    /// Loads the cached index of `roots` and brings it up to date
+    pub fn load(roots: &[PathBuf]) -> io::Result<Library> {
+        let mut library = Library::open(roots);
+        library.rescan()?;
+        Ok(library)
+    }
+
+    /// Loads the cached index of `roots` as is, which may be outdated or empty until `rescan`
+    pub fn open(roots: &[PathBuf]) -> Library {
+        Library::open_cached(roots, cache_path().ok())
+    }
+
+    fn open_cached(roots: &[PathBuf], cache: Option<PathBuf>) -> Library {
+        let mut library = cache
+            .as_deref()
+            .and_then(read_cache)
+            .filter(|library| library.version == CACHE_VERSION && library.roots == roots)
+            .unwrap_or_else(|| Library {
+                version: CACHE_VERSION,
+                roots: roots.to_vec(),
+                wallpapers: BTreeMap::new(),
+                cache: None,
+            });
+        library.cache = cache;
+
+        library
+    }
+
+    /// Indexes the wallpapers added or modified since the last scan and forgets the removed
+    /// ones, then updates the cache if anything changed.
+    ///
+    /// Roots which can't be read, like a library on an unmounted disk, are skipped. Fails only
+    /// when none of them can be read.
+    pub fn rescan(&mut self) -> io::Result<()> {
+        let mut wallpapers = BTreeMap::new();
+        let mut changed = false;
+
+        let mut entries = vec![];
+        let mut error = None;
+        for root in &self.roots {
+            match fs::read_dir(root) {
+                Ok(dir) => entries.push(dir),
+                Err(err) => {
+                    tracing::warn!(
+                        "Skipping unreadable workshop directory {}: {}",
+                        root.to_string_lossy(),
+                        err
+                    );
+                    error = Some(err);
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            if let Some(err) = error {
+                return Err(err);
+            }
+        }
+
+        for entry in entries.into_iter().flatten().flatten() {
+            // Workshop items are stored in directories named after their id
+            let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+
+            // The first workshop directory containing it wins
+            if wallpapers.contains_key(&id) {
+                continue;
+            }
+
+            let path = entry.path();
+            let Some(modified) = last_modification(&path) else {
+                continue;
+            };
+
+            match self.wallpapers.remove(&id) {
+                Some(cached) if cached.modified == modified && cached.path == path => {
+                    wallpapers.insert(id, cached);
+                }
+                _ => {
+                    changed = true;
+
+                    if let Some(wallpaper) = index_wallpaper(id, &path, modified) {
+                        wallpapers.insert(id, wallpaper);
+                    }
+                }
+            }
+        }
+
+        // Whatever is left wasn't found anymore
+        changed |= !self.wallpapers.is_empty();
+        self.wallpapers = wallpapers;
+
+        if changed {
+            // The cache only saves time, the index is still usable without it
+            let _ = self.write_cache();
+        }
+
+        Ok(())
+    }
+
+    /// The workshop directories this library indexes
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn get(&self, id: u64) -> Option<&LibraryEntry> {
+        self.wallpapers.get(&id)
+    }
+
+    /// The indexed wallpapers, sorted by id
+    pub fn wallpapers(&self) -> impl Iterator<Item = &LibraryEntry> {
+        self.wallpapers.values()
+    }
+
+    fn write_cache(&self) -> io::Result<()> {
+        let Some(path) = &self.cache else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // The daemon and the clients may rescan at the same time, never leave a partial file
+        let temp_path = path.with_extension(format!("json.{}", std::process::id()));
+        let file = File::create(&temp_path)?;
+        serde_json::to_writer(file, self).map_err(io::Error::from)?;
+
+        fs::rename(temp_path, path)
+    }
+}
+
+/// Path of the library cache: `$XDG_CACHE_HOME/waypaper_engine/library.json`
+pub fn cache_path() -> io::Result<PathBuf> {
+    let cache_dir = match env::var("XDG_CACHE_HOME") {
+        Ok(cache) => PathBuf::from(cache),
+        Err(_) => PathBuf::from(env::var("HOME").map_err(|_| {
+            io::Error::new(io::ErrorKind::NotFound, "No HOME environment variable set")
+        })?)
+        .join(".cache"),
+    };
+
+    Ok(cache_dir.join("waypaper_engine").join("library.json"))
+}
+
+fn read_cache(path: &Path) -> Option<Library> {
+    let file = File::open(path).ok()?;
+    serde_json::from_reader(io::BufReader::new(file)).ok()
+}
+
+/// Most recent modification of the wallpaper directory (files added or removed) and of its
+/// project.json. `None` if the directory has no project.json.
+fn last_modification(path: &Path) -> Option<u64> {
+    let project = fs::metadata(path.join("project.json")).ok()?;
+    let directory = fs::metadata(path).ok()?;
+
+    [project.modified().ok()?, directory.modified().ok()?]
+        .into_iter()
+        .max()
+        .map(unix_seconds)
+}
+
+fn index_wallpaper(id: u64, path: &Path, modified: u64) -> Option<LibraryEntry> {
+    let project = WEProject::new(&path.join("project.json"), Some(id)).ok()?;
+
+    Some(LibraryEntry {
+        id,
+        title: project.title,
+        wallpaper_type: project.wallpaper_type,
+        tags: project.tags.unwrap_or_default(),
+        content_rating: project.content_rating,
+        path: path.to_path_buf(),
+        preview: (!project.preview.is_empty()).then(|| path.join(project.preview)),
+        size: directory_size(path),
+        modified,
+    })
+}
+
+fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => {
+                entry.metadata().map_or(0, |metadata| metadata.len())
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Workshop directories and a library cache, removed when dropped
+    struct FakeWorkshop {
+        dir: PathBuf,
+    }
+
+    impl FakeWorkshop {
+        fn new(name: &str) -> FakeWorkshop {
+            let dir = env::temp_dir().join(format!(
+                "waypaper_engine_library_{}_{}",
+                process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+
+            FakeWorkshop { dir }
+        }
+
+        fn root(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+
+        fn cache(&self) -> Option<PathBuf> {
+            Some(self.dir.join("library.json"))
+        }
+
+        /// Writes the project.json of a wallpaper, then dates it and its directory at `modified`
+        fn wallpaper(&self, root: &str, id: &str, title: &str, modified: u64) -> &FakeWorkshop {
+            let path = self.root(root).join(id);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(
+                path.join("project.json"),
+                format!(r#"{{"title": "{title}", "type": "scene", "preview": "preview.jpg"}}"#),
+            )
+            .unwrap();
+
+            set_modified(&path.join("project.json"), modified);
+            set_modified(&path, modified);
+            self
+        }
+
+        fn open(&self, roots: &[&str]) -> Library {
+            let roots: Vec<_> = roots.iter().map(|root| self.root(root)).collect();
+            Library::open_cached(&roots, self.cache())
+        }
+    }
+
+    impl Drop for FakeWorkshop {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn set_modified(path: &Path, modified: u64) {
+        File::open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+    }
+
+    fn titles(library: &Library) -> Vec<(u64, &str)> {
+        library
+            .wallpapers()
+            .map(|entry| (entry.id, entry.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_wallpapers_come_from_the_cache() {
+        let workshop = FakeWorkshop::new("cache");
+        workshop
+            .wallpaper("a", "1", "Waves", 1000)
+            .wallpaper("a", "2", "Stars", 1000);
+
+        let mut library = workshop.open(&["a"]);
+        assert!(titles(&library).is_empty());
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves"), (2, "Stars")]);
+        assert_eq!(library.get(1).unwrap().modified, 1000);
+        assert_eq!(
+            library.get(1).unwrap().preview,
+            Some(workshop.root("a").join("1/preview.jpg"))
+        );
+
+        // An edit which kept the modification time isn't seen
+        workshop.wallpaper("a", "1", "Ocean waves", 1000);
+        let mut library = workshop.open(&["a"]);
+        assert_eq!(titles(&library), [(1, "Waves"), (2, "Stars")]);
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves"), (2, "Stars")]);
+
+        workshop.wallpaper("a", "1", "Ocean waves", 2000);
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Ocean waves"), (2, "Stars")]);
+        assert_eq!(library.get(1).unwrap().modified, 2000);
+
+        // The cache was updated as well
+        assert_eq!(
+            titles(&workshop.open(&["a"])),
+            [(1, "Ocean waves"), (2, "Stars")]
+        );
+    }
+
+    #[test]
+    fn removed_wallpapers_are_dropped() {
+        let workshop = FakeWorkshop::new("removed");
+        workshop
+            .wallpaper("a", "1", "Waves", 1000)
+            .wallpaper("a", "2", "Stars", 1000);
+        // Neither a workshop item nor a wallpaper
+        fs::create_dir_all(workshop.root("a").join("downloads")).unwrap();
+        fs::create_dir_all(workshop.root("a").join("3")).unwrap();
+
+        let mut library = workshop.open(&["a"]);
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves"), (2, "Stars")]);
+
+        fs::remove_dir_all(workshop.root("a").join("2")).unwrap();
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves")]);
+        assert_eq!(titles(&workshop.open(&["a"])), [(1, "Waves")]);
+    }
+
+    #[test]
+    fn first_root_wins() {
+        let workshop = FakeWorkshop::new("roots");
+        workshop
+            .wallpaper("a", "1", "Waves from a", 1000)
+            .wallpaper("b", "1", "Waves from b", 1000)
+            .wallpaper("b", "2", "Stars", 1000);
+
+        let mut library = workshop.open(&["a", "b"]);
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves from a"), (2, "Stars")]);
+        assert_eq!(library.get(1).unwrap().path, workshop.root("a").join("1"));
+
+        // Other roots don't use the cache of these ones
+        let mut library = workshop.open(&["b", "a"]);
+        assert!(titles(&library).is_empty());
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves from b"), (2, "Stars")]);
+    }
+
+    #[test]
+    fn unreadable_roots_are_skipped() {
+        let workshop = FakeWorkshop::new("unreadable");
+        workshop.wallpaper("a", "1", "Waves", 1000);
+
+        let mut library = workshop.open(&["unmounted", "a"]);
+        library.rescan().unwrap();
+        assert_eq!(titles(&library), [(1, "Waves")]);
+
+        let mut library = workshop.open(&["unmounted"]);
+        assert_eq!(
+            library.rescan().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
+use std::io;
 use std::path::Path;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 use serde_this_or_that::{as_bool, as_f64, as_i64, as_opt_string, as_opt_u64};
@@ -22,7 +25,7 @@ pub struct WEProject {
 
     pub approved: Option<bool>,
 
-    #[serde(rename = "lowercase")]
+    #[serde(rename = "contentrating")]
     pub content_rating: Option<String>,
     pub description: Option<String>,
 
@@ -115,12 +118,12 @@ pub enum PropertyValue {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComboOption {
     #[serde(deserialize_with = "as_opt_string")]
-    value: Option<String>,
-    label: String,
+    pub value: Option<String>,
+    pub label: String,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(tag = "type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum WallpaperType {
     #[default]
     Preset,
@@ -131,6 +134,48 @@ pub enum WallpaperType {
     Web,
 }
 
+impl WallpaperType {
+    pub const ALL: [WallpaperType; 4] = [
+        WallpaperType::Preset,
+        WallpaperType::Video,
+        WallpaperType::Scene,
+        WallpaperType::Web,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WallpaperType::Preset => "preset",
+            WallpaperType::Video => "video",
+            WallpaperType::Scene => "scene",
+            WallpaperType::Web => "web",
+        }
+    }
+}
+
+impl Display for WallpaperType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for WallpaperType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WallpaperType::ALL
+            .into_iter()
+            .find(|wp_type| wp_type.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = WallpaperType::ALL.iter().map(|t| t.name()).collect();
+                format!(
+                    "unknown wallpaper type {} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "mode", rename_all = "lowercase")]
 pub enum DirMode {
@@ -160,14 +205,332 @@ pub enum PresetValue {
 }
 
 impl WEProject {
-    pub fn new(path: &Path, id: u64) -> Self {
-        let project_file = File::open(path).unwrap();
-        let mut proj: WEProject = serde_json::from_reader(project_file).unwrap();
+    /// `id` is used as the workshop id when the project doesn't specify one
+    pub fn new(path: &Path, id: Option<u64>) -> io::Result<Self> {
+        let project_file = File::open(path)?;
+        let mut proj: WEProject = serde_json::from_reader(project_file)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
         if proj.workshop_id.is_none() {
-            proj.workshop_id = Some(id);
+            proj.workshop_id = id;
+        }
+
+        Ok(proj)
+    }
+}
+
+impl PropertyValue {
+    /// Parses `value` as a new value for this property, checking it against the property
+    /// constraints (slider range, combo options, ...)
+    pub fn with_value(&self, value: &str) -> Result<PropertyValue, PropertyValueError> {
+        let value = value.trim();
+
+        Ok(match self {
+            PropertyValue::Color { .. } => {
+                let (r, g, b) = parse_color(value)
+                    .ok_or_else(|| PropertyValueError::InvalidColor(value.to_owned()))?;
+                PropertyValue::Color { r, g, b }
+            }
+            PropertyValue::Slider {
+                min,
+                max,
+                precision,
+                step,
+                ..
+            } => {
+                let number = f64::from_str(value)
+                    .map_err(|_| PropertyValueError::NotANumber(value.to_owned()))?;
+
+                if !(*min..=*max).contains(&number) {
+                    return Err(PropertyValueError::OutOfRange {
+                        value: number,
+                        min: *min,
+                        max: *max,
+                    });
+                }
+
+                PropertyValue::Slider {
+                    min: *min,
+                    max: *max,
+                    precision: *precision,
+                    step: *step,
+                    value: number,
+                }
+            }
+            PropertyValue::Combo { options, .. } => {
+                let index = options
+                    .iter()
+                    .position(|option| {
+                        option.value.as_deref() == Some(value) || option.label == value
+                    })
+                    .ok_or_else(|| PropertyValueError::UnknownOption(value.to_owned()))?;
+
+                // Combo values are numeric most of the time, fall back on the option index
+                let selected = options[index]
+                    .value
+                    .as_deref()
+                    .and_then(|value| i64::from_str(value).ok())
+                    .unwrap_or(index as i64);
+
+                PropertyValue::Combo {
+                    options: options.clone(),
+                    value: selected,
+                }
+            }
+            PropertyValue::Bool { .. } => PropertyValue::Bool {
+                value: match value {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => return Err(PropertyValueError::NotABool(value.to_owned())),
+                },
+            },
+            PropertyValue::TextInput { .. } => PropertyValue::TextInput {
+                value: value.to_owned(),
+            },
+            PropertyValue::File { .. } => PropertyValue::File {
+                value: Some(value.to_owned()),
+            },
+            PropertyValue::Text {} | PropertyValue::Directory { .. } => {
+                return Err(PropertyValueError::ReadOnly);
+            }
+        })
+    }
+}
+
+/// Accepts Wallpaper Engine colors ("0.1 0.5 1") as well as hex colors ("#1a80ff")
+fn parse_color(value: &str) -> Option<(f64, f64, f64)> {
+    if let Some(hex) = value.strip_prefix('#') {
+        // from_str_radix would take a sign as well
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .ok()
+                .map(|c| f64::from(c) / 255.0)
+        };
+
+        return Some((channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    let parts = value
+        .split_whitespace()
+        .map(|part| f64::from_str(part).ok().filter(|c| (0.0..=1.0).contains(c)))
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts[..] {
+        [r, g, b] => Some((r, g, b)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValueError {
+    NotANumber(String),
+    NotABool(String),
+    InvalidColor(String),
+    OutOfRange { value: f64, min: f64, max: f64 },
+    UnknownOption(String),
+    ReadOnly,
+}
+
+impl Display for PropertyValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyValueError::NotANumber(value) => write!(f, "{} is not a number", value),
+            PropertyValueError::NotABool(value) => {
+                write!(f, "{} is not a boolean (expected true or false)", value)
+            }
+            PropertyValueError::InvalidColor(value) => write!(
+                f,
+                "{} is not a color (expected \"r g b\" between 0 and 1 or #rrggbb)",
+                value
+            ),
+            PropertyValueError::OutOfRange { value, min, max } => {
+                write!(f, "{} is out of range [{}, {}]", value, min, max)
+            }
+            PropertyValueError::UnknownOption(value) => {
+                write!(f, "{} is not one of the property options", value)
+            }
+            PropertyValueError::ReadOnly => write!(f, "this property cannot be changed"),
+        }
+    }
+}
+
+impl std::error::Error for PropertyValueError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slider() -> PropertyValue {
+        PropertyValue::Slider {
+            min: 0.0,
+            max: 2.0,
+            precision: None,
+            step: None,
+            value: 1.0,
+        }
+    }
+
+    fn combo(options: &[(Option<&str>, &str)]) -> PropertyValue {
+        PropertyValue::Combo {
+            options: options
+                .iter()
+                .map(|(value, label)| ComboOption {
+                    value: value.map(str::to_owned),
+                    label: (*label).to_owned(),
+                })
+                .collect(),
+            value: 0,
+        }
+    }
+
+    fn combo_value(property: &PropertyValue, value: &str) -> Result<i64, PropertyValueError> {
+        match property.with_value(value)? {
+            PropertyValue::Combo { value, .. } => Ok(value),
+            other => panic!("{other:?} is not a combo"),
+        }
+    }
+
+    fn color(value: &str) -> Result<(f64, f64, f64), PropertyValueError> {
+        let color = PropertyValue::Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        match color.with_value(value)? {
+            PropertyValue::Color { r, g, b } => Ok((r, g, b)),
+            other => panic!("{other:?} is not a color"),
         }
+    }
+
+    #[test]
+    fn slider_range() {
+        for (input, expected) in [("0", 0.0), ("2", 2.0), (" 1.5 ", 1.5)] {
+            match slider().with_value(input) {
+                Ok(PropertyValue::Slider { value, .. }) => assert_eq!(value, expected),
+                other => panic!("{input}: {other:?}"),
+            }
+        }
+
+        assert_eq!(
+            slider().with_value("2.5").unwrap_err(),
+            PropertyValueError::OutOfRange {
+                value: 2.5,
+                min: 0.0,
+                max: 2.0
+            }
+        );
+        assert!(matches!(
+            slider().with_value("-0.1"),
+            Err(PropertyValueError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            slider().with_value("inf"),
+            Err(PropertyValueError::OutOfRange { .. })
+        ));
+        // NaN isn't equal to itself, the error can only be matched
+        assert!(matches!(
+            slider().with_value("NaN"),
+            Err(PropertyValueError::OutOfRange { value, .. }) if value.is_nan()
+        ));
+        assert_eq!(
+            slider().with_value("one").unwrap_err(),
+            PropertyValueError::NotANumber("one".to_owned())
+        );
+    }
+
+    #[test]
+    fn combo_options() {
+        let numeric = combo(&[
+            (Some("0"), "Off"),
+            (Some("5"), "Slow"),
+            (Some("10"), "Fast"),
+        ]);
+        // By value or by label, the value is kept
+        assert_eq!(combo_value(&numeric, "5"), Ok(5));
+        assert_eq!(combo_value(&numeric, "Fast"), Ok(10));
+        // Option indices aren't values
+        assert_eq!(
+            combo_value(&numeric, "1"),
+            Err(PropertyValueError::UnknownOption("1".to_owned()))
+        );
+        assert_eq!(
+            combo_value(&numeric, "fast"),
+            Err(PropertyValueError::UnknownOption("fast".to_owned()))
+        );
+
+        // Non numeric and missing values fall back on the option index
+        let named = combo(&[
+            (Some("red"), "Red"),
+            (None, "Green"),
+            (Some("blue"), "Blue"),
+        ]);
+        assert_eq!(combo_value(&named, "blue"), Ok(2));
+        assert_eq!(combo_value(&named, "Red"), Ok(0));
+        assert_eq!(combo_value(&named, "Green"), Ok(1));
+
+        // A label matching the value of an earlier option selects that option
+        let ambiguous = combo(&[(Some("1"), "2"), (Some("2"), "1")]);
+        assert_eq!(combo_value(&ambiguous, "2"), Ok(1));
+    }
+
+    #[test]
+    fn bool_spellings() {
+        for (input, expected) in [("true", true), ("1", true), ("false", false), ("0", false)] {
+            match (PropertyValue::Bool { value: !expected }).with_value(input) {
+                Ok(PropertyValue::Bool { value }) => assert_eq!(value, expected, "{input}"),
+                other => panic!("{input}: {other:?}"),
+            }
+        }
+
+        assert_eq!(
+            (PropertyValue::Bool { value: false })
+                .with_value("yes")
+                .unwrap_err(),
+            PropertyValueError::NotABool("yes".to_owned())
+        );
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(color("0.1 0.5 1"), Ok((0.1, 0.5, 1.0)));
+        assert_eq!(color("#ff0080"), Ok((1.0, 0.0, 128.0 / 255.0)));
+        assert_eq!(color("#FF0080"), color("#ff0080"));
+
+        for input in [
+            "0.1 0.5",
+            "0.1 0.5 1 1",
+            "0.1 0.5 1.5",
+            "#ff008",
+            "#ff00800",
+            "#gg0080",
+            "#+1+2+3",
+            "#ééé",
+            "ff0080",
+        ] {
+            assert_eq!(
+                color(input),
+                Err(PropertyValueError::InvalidColor(input.to_owned())),
+                "{input}"
+            );
+        }
+    }
 
-        proj
+    #[test]
+    fn read_only_kinds() {
+        let directory = PropertyValue::Directory {
+            mode: DirMode::OnDemand,
+        };
+        assert_eq!(
+            PropertyValue::Text {}.with_value("x").unwrap_err(),
+            PropertyValueError::ReadOnly
+        );
+        assert_eq!(
+            directory.with_value("/tmp").unwrap_err(),
+            PropertyValueError::ReadOnly
+        );
     }
 }
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 
 use cgmath::{Vector2, Vector3};
 use serde::de::{Error, Unexpected};
 use serde::{Deserialize, Deserializer};
 
+use crate::config::MIN_FRAME_BUFFER_SIZE;
 use crate::project::WallpaperType;
 
 pub fn from_map_str_color<'de, D>(deserializer: D) -> Result<(f64, f64, f64), D::Error>
@@ -66,6 +68,39 @@ where
     }
 }
 
+pub fn as_percentage<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: u8 = Deserialize::deserialize(deserializer)?;
+
+    if value > 100 {
+        return Err(Error::invalid_value(
+            Unexpected::Unsigned(value.into()),
+            &"a percentage between 0 and 100",
+        ));
+    }
+
+    Ok(value)
+}
+
+pub fn as_frame_buffer_size<'de, D>(deserializer: D) -> Result<NonZeroUsize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: usize = Deserialize::deserialize(deserializer)?;
+
+    // The video renderer waits for this many frames to be queued before showing one
+    if value < MIN_FRAME_BUFFER_SIZE {
+        return Err(Error::invalid_value(
+            Unexpected::Unsigned(value as u64),
+            &format!("at least {}", MIN_FRAME_BUFFER_SIZE).as_str(),
+        ));
+    }
+
+    Ok(NonZeroUsize::new(value).expect("The size was checked not to be 0"))
+}
+
 pub fn as_wp_type<'de, D>(deserializer: D) -> Result<WallpaperType, D::Error>
 where
     D: Deserializer<'de>,
@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const WALLPAPER_ENGINE_APP_ID: &str = "431960";
+
+/// Overrides the workshop directories, as a `:` separated list like `PATH`
+pub const WORKSHOP_DIR_ENV: &str = "WAYPAPER_ENGINE_WORKSHOP_DIR";
+
+// Where the Steam installations usually live, relative to the home directory
+const STEAM_ROOTS: [&str; 5] = [
+    ".steam/steam",
+    ".local/share/Steam",
+    ".steam/debian-installation",
+    // Flatpak
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.steam/steam",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkshopDirError {
+    NoHome,
+    /// A directory given explicitly doesn't exist
+    InvalidOverride(PathBuf),
+    /// No Steam library contains Wallpaper Engine workshop items
+    NotFound {
+        searched: Vec<PathBuf>,
+    },
+}
+
+impl Display for WorkshopDirError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkshopDirError::NoHome => write!(f, "no HOME environment variable set"),
+            WorkshopDirError::InvalidOverride(path) => write!(
+                f,
+                "workshop directory {} is not a directory",
+                path.to_string_lossy()
+            ),
+            WorkshopDirError::NotFound { searched } => {
+                write!(
+                    f,
+                    "Wallpaper Engine workshop folder not found (searched Steam libraries: "
+                )?;
+                if searched.is_empty() {
+                    write!(f, "none found")?;
+                }
+                for (i, library) in searched.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", library.to_string_lossy())?;
+                }
+                write!(f, "), set {} to override it", WORKSHOP_DIR_ENV)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkshopDirError {}
+
+/// Finds the Wallpaper Engine workshop directories: `overrides` if not empty, then the
+/// directories listed in `$WAYPAPER_ENGINE_WORKSHOP_DIR`, and otherwise the ones of every
+/// Steam library containing Wallpaper Engine
+pub fn find_workshop_dirs(overrides: &[PathBuf]) -> Result<Vec<PathBuf>, WorkshopDirError> {
+    let env_overrides: Vec<PathBuf> = env::var_os(WORKSHOP_DIR_ENV)
+        .map(|dirs| env::split_paths(&dirs).collect())
+        .unwrap_or_default();
+
+    let overrides = if overrides.is_empty() {
+        &env_overrides
+    } else {
+        overrides
+    };
+
+    if !overrides.is_empty() {
+        return match overrides.iter().find(|dir| !dir.is_dir()) {
+            Some(dir) => Err(WorkshopDirError::InvalidOverride(dir.clone())),
+            None => Ok(overrides.to_vec()),
+        };
+    }
+
+    let home = PathBuf::from(env::var_os("HOME").ok_or(WorkshopDirError::NoHome)?);
+    let libraries = steam_libraries(&home);
+
+    let workshop_dirs: Vec<PathBuf> = libraries
+        .iter()
+        .map(|library| {
+            library
+                .join("steamapps/workshop/content")
+                .join(WALLPAPER_ENGINE_APP_ID)
+        })
+        .filter(|dir| dir.is_dir())
+        .collect();
+
+    if workshop_dirs.is_empty() {
+        return Err(WorkshopDirError::NotFound {
+            searched: libraries,
+        });
+    }
+
+    Ok(workshop_dirs)
+}
+
+/// Every Steam library of the Steam installations found in `home`, without duplicates
+fn steam_libraries(home: &Path) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut libraries = vec![];
+
+    for root in STEAM_ROOTS.iter().map(|root| home.join(root)) {
+        // The Steam installation is a library itself, even when the vdf doesn't list it
+        let mut candidates = vec![root.clone()];
+        candidates.extend(
+            ["steamapps/libraryfolders.vdf", "config/libraryfolders.vdf"]
+                .iter()
+                .filter_map(|file| fs::read_to_string(root.join(file)).ok())
+                .flat_map(|content| library_folders(&content)),
+        );
+
+        for library in candidates {
+            // ~/.steam/steam is usually a symlink to one of the other roots
+            let Ok(canonical) = library.canonicalize() else {
+                continue;
+            };
+
+            if seen.insert(canonical) {
+                libraries.push(library);
+            }
+        }
+    }
+
+    libraries
+}
+
+/// Parses the library paths of a `libraryfolders.vdf` file, skipping the libraries known not
+/// to contain Wallpaper Engine
+fn library_folders(content: &str) -> Vec<PathBuf> {
+    let Some(VdfValue::Object(root)) = parse_vdf(content) else {
+        return vec![];
+    };
+
+    let Some((_, VdfValue::Object(folders))) = root
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("libraryfolders"))
+    else {
+        return vec![];
+    };
+
+    folders
+        .iter()
+        .filter(|(key, _)| key.parse::<u32>().is_ok())
+        .filter_map(|(_, folder)| match folder {
+            // Old format, the entry is the path itself
+            VdfValue::String(path) => Some(PathBuf::from(path)),
+            VdfValue::Object(fields) => {
+                let field = |name: &str| {
+                    fields
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                        .map(|(_, value)| value)
+                };
+
+                let installed = match field("apps") {
+                    Some(VdfValue::Object(apps)) => {
+                        apps.iter().any(|(app, _)| app == WALLPAPER_ENGINE_APP_ID)
+                    }
+                    _ => true,
+                };
+
+                match field("path") {
+                    Some(VdfValue::String(path)) if installed => Some(PathBuf::from(path)),
+                    _ => None,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Value of Valve's KeyValues text format, used by the Steam configuration files
+#[derive(Debug, Clone, PartialEq)]
+enum VdfValue {
+    String(String),
+    Object(Vec<(String, VdfValue)>),
+}
+
+#[derive(Debug, PartialEq)]
+enum VdfToken {
+    String(String),
+    Open,
+    Close,
+}
+
+/// Parses the whole file as the content of an object, `None` if it is malformed
+fn parse_vdf(content: &str) -> Option<VdfValue> {
+    let mut tokens = tokenize_vdf(content)?.into_iter();
+    let object = parse_vdf_object(&mut tokens, false)?;
+
+    Some(VdfValue::Object(object))
+}
+
+fn parse_vdf_object(
+    tokens: &mut impl Iterator<Item = VdfToken>,
+    nested: bool,
+) -> Option<Vec<(String, VdfValue)>> {
+    let mut fields = vec![];
+
+    loop {
+        let key = match tokens.next() {
+            Some(VdfToken::String(key)) => key,
+            Some(VdfToken::Close) if nested => return Some(fields),
+            None if !nested => return Some(fields),
+            _ => return None,
+        };
+
+        let value = match tokens.next()? {
+            VdfToken::String(value) => VdfValue::String(value),
+            VdfToken::Open => VdfValue::Object(parse_vdf_object(tokens, true)?),
+            VdfToken::Close => return None,
+        };
+
+        fields.push((key, value));
+    }
+}
+
+fn tokenize_vdf(content: &str) -> Option<Vec<VdfToken>> {
+    let mut tokens = vec![];
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(VdfToken::Open),
+            '}' => tokens.push(VdfToken::Close),
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            'n' => string.push('\n'),
+                            't' => string.push('\t'),
+                            escaped => string.push(escaped),
+                        },
+                        c => string.push(c),
+                    }
+                }
+                tokens.push(VdfToken::String(string));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            // Unquoted strings
+            c => {
+                let mut string = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' || c == '{' || c == '}' {
+                        break;
+                    }
+                    string.push(c);
+                    chars.next();
+                }
+                tokens.push(VdfToken::String(string));
+            }
+        }
+    }
+
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_library_folders_layout() {
+        let content = r#"
+"LibraryFolders"
+{
+	"TimeNextStatsReport"		"1640995200"
+	"ContentStatsID"		"-1234567890"
+	"1"		"/mnt/games/SteamLibrary"
+	"2"		"/home/user/Other Library"
+}
+"#;
+
+        assert_eq!(
+            library_folders(content),
+            [
+                PathBuf::from("/mnt/games/SteamLibrary"),
+                PathBuf::from("/home/user/Other Library")
+            ]
+        );
+    }
+
+    #[test]
+    fn new_library_folders_layout() {
+        let content = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+		"contentid"		"123456789"
+		"totalsize"		"0"
+		"apps"
+		{
+			"228980"		"300000000"
+			"431960"		"1000000000"
+		}
+	}
+	// A library without Wallpaper Engine
+	"1"
+	{
+		"path"		"/mnt/games/SteamLibrary"
+		"apps"
+		{
+			"570"		"40000000000"
+		}
+	}
+	"2"
+	{
+		"path"		"/mnt/other"
+	}
+}
+"#;
+
+        // Libraries which don't list their apps may contain it
+        assert_eq!(
+            library_folders(content),
+            [
+                PathBuf::from("/home/user/.local/share/Steam"),
+                PathBuf::from("/mnt/other")
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_strings() {
+        let content = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"D:\\SteamLibrary \"games\""
+	}
+}
+"#;
+
+        assert_eq!(
+            library_folders(content),
+            [PathBuf::from(r#"D:\SteamLibrary "games""#)]
+        );
+    }
+
+    #[test]
+    fn unquoted_strings() {
+        assert_eq!(
+            parse_vdf("key { nested value }"),
+            Some(VdfValue::Object(vec![(
+                "key".to_owned(),
+                VdfValue::Object(vec![(
+                    "nested".to_owned(),
+                    VdfValue::String("value".to_owned())
+                )])
+            )]))
+        );
+    }
+
+    #[test]
+    fn malformed_files() {
+        for content in [
+            r#""libraryfolders" { "0" { "path" "/mnt/games" }"#,
+            r#""libraryfolders" { "0" "/mnt/games" } }"#,
+            r#""libraryfolders" { "0" "/mnt/games }"#,
+            r#""libraryfolders" { "0" }"#,
+            r#""libraryfolders""#,
+            r#"{ "0" "/mnt/games" }"#,
+        ] {
+            assert_eq!(parse_vdf(content), None, "{content} was parsed");
+            assert!(library_folders(content).is_empty());
+        }
+
+        // Valid, but not a library folders file
+        assert!(library_folders(r#""config" { "0" "/mnt/games" }"#).is_empty());
+        assert!(library_folders("").is_empty());
+    }
+}
@@ -1,8 +1,8 @@
-use std::fs;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 use anyhow::Error;
@@ -12,8 +12,10 @@ use serde::Serialize;
 use tauri::{Emitter, State, Window};
 use xrandr_parser::Parser;
 
+use waypaper_engine_shared::config::Config;
 use waypaper_engine_shared::ipc::{IPCRequest, IPCResponse};
-use waypaper_engine_shared::project::{WEProject, WallpaperType};
+use waypaper_engine_shared::library::Library;
+use waypaper_engine_shared::project::WallpaperType;
 
 #[tauri::command]
 fn stop_daemon(channel: State<Mutex<IpcChannel>>) {
@@ -66,54 +68,43 @@ struct WPInfo {
 }
 
 #[tauri::command]
-fn loaded(
-    window: Window,
-    wallpapers: State<Mutex<Vec<WEProject>>>,
-    wallpaper_infos: State<Mutex<Vec<WPInfo>>>,
-) {
-    let mut wallpapers = wallpapers.lock().unwrap();
-    let wpe_dir = waypaper_engine_shared::get_wpe_dir();
-
-    for entry in fs::read_dir(wpe_dir.clone()).unwrap().flatten() {
-        if let Ok(aa) = File::open(entry.path().join("project.json")) {
-            if let Ok(mut project) = serde_json::from_reader::<File, WEProject>(aa) {
-                if project.workshop_id.is_none() {
-                    project.workshop_id = entry.file_name().to_str().and_then(|s| s.parse().ok());
-                }
-
-                if project.wallpaper_type == WallpaperType::Video {
-                    wallpapers.push(project);
-                }
-            }
+fn loaded(window: Window, wallpaper_infos: State<Mutex<Vec<WPInfo>>>) {
+    let config = Config::load(None).unwrap_or_default();
+    let wpe_dirs = match waypaper_engine_shared::get_wpe_dirs(&config.workshop_dirs) {
+        Ok(wpe_dirs) => wpe_dirs,
+        Err(e) => {
+            eprintln!("Unable to find the wallpapers: {}", e);
+            return;
         }
-    }
-
-    wallpapers.retain(|wp| {
-        wp.wallpaper_type == WallpaperType::Video || wp.wallpaper_type == WallpaperType::Scene
-    });
+    };
+    let library = match Library::load(&wpe_dirs) {
+        Ok(library) => library,
+        Err(e) => {
+            eprintln!("Unable to scan the wallpaper library: {}", e);
+            return;
+        }
+    };
 
     let mut wallpaper_infos = wallpaper_infos.lock().unwrap();
-    wallpapers
-        .iter()
-        .flat_map(|project| {
-            let id = project.workshop_id.unwrap();
-
-            let preview_path = wpe_dir.join(id.to_string()).join(&project.preview);
-
-            match to_base64(&preview_path) {
-                Ok(b64) => {
-                    let title = project.title.clone();
-
-                    Some(WPInfo {
-                        id,
-                        title,
-                        preview_b64: b64,
-                    })
-                }
+    library
+        .wallpapers()
+        .filter(|wp| wp.wallpaper_type == WallpaperType::Video)
+        .flat_map(|wp| {
+            let Some(preview_path) = wp.preview.as_ref() else {
+                eprintln!("Wallpaper {} has no preview", wp.title);
+                return None;
+            };
+
+            match to_base64(preview_path) {
+                Ok(b64) => Some(WPInfo {
+                    id: wp.id,
+                    title: wp.title.clone(),
+                    preview_b64: b64,
+                }),
                 Err(e) => {
                     eprintln!(
                         "Failed to convert preview to base64 for file {:?} (wallpaper: {}): {}",
-                        preview_path, project.title, e
+                        preview_path, wp.title, e
                     );
                     None
                 }
@@ -125,11 +116,21 @@ fn loaded(
 }
 
 fn main() -> Result<(), Error> {
-    let wallpapers: Mutex<Vec<WEProject>> = Mutex::new(vec![]);
     let wallpaper_infos: Mutex<Vec<WPInfo>> = Mutex::new(vec![]);
 
+    let socket_path = env::args()
+        .skip_while(|arg| arg != "--socket")
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Config::load(None).unwrap_or_default().socket_path());
+
     let channel = Mutex::new(
-        IpcChannel::connect("/tmp/waypaper-engine.sock").expect("Failed to connect to daemon"),
+        IpcChannel::connect(
+            socket_path
+                .to_str()
+                .expect("IPC socket path is not valid UTF-8"),
+        )
+        .expect("Failed to connect to daemon"),
     );
 
     tauri::Builder::default()
@@ -141,7 +142,6 @@ fn main() -> Result<(), Error> {
             apply_filter,
             stop_daemon,
         ])
-        .manage(wallpapers)
         .manage(wallpaper_infos)
         .manage(channel)
         .manage(Mutex::new(Parser::new()))